@@ -1,11 +1,26 @@
 use std::ffi::{OsStr, OsString};
+use std::time::SystemTime;
 
-use clap::{SubCommand, Arg, AppSettings, App};
+use chrono::format::{Item, StrftimeItems};
+use clap::{SubCommand, Arg, ArgGroup, AppSettings, App};
+
+use crate::filter::{parse_size, parse_time};
+use crate::translit::LOCALES;
 
 
 
 pub const FROM_FILE_SUBCOMMAND: &str = "from-file"; 
 pub const TO_ASCII_SUBCOMMMAND: &str = "to-ascii"; 
+pub const EDIT_SUBCOMMAND: &str = "edit"; 
+pub const FROM_CSV_SUBCOMMAND: &str = "from-csv"; 
+pub const TEMPLATE_SUBCOMMAND: &str = "template"; 
+pub const URL_DECODE_SUBCOMMAND: &str = "url-decode"; 
+pub const URL_ENCODE_SUBCOMMAND: &str = "url-encode"; 
+pub const TIDY_SUBCOMMAND: &str = "tidy"; 
+pub const MEDIA_SUBCOMMAND: &str = "media"; 
+pub const DATES_SUBCOMMAND: &str = "dates"; 
+pub const SEQUENCE_SUBCOMMAND: &str = "sequence"; 
+pub const POSITION_SUBCOMMAND: &str = "position"; 
 
 
 //create the application here
@@ -48,7 +63,7 @@ pub fn create_app<'a>() -> App<'a,'a> {
 
     let path_args = [
         Arg::with_name("PATH(S)")
-            .help("Target paths")
+            .help("Target paths, use '-' to read them from stdin, 'PATH:MIN..MAX' sets the depth of a root in recursive mode")
             .validator_os(is_valid_string)
             .multiple(true)
             .required(true),
@@ -56,7 +71,7 @@ pub fn create_app<'a>() -> App<'a,'a> {
             .long("include-dirs")
             .short("D")
             .group("TEST")
-            .help("Rename matching directories"),
+            .help("Rename matching directories too, same as adding '--type d'"),
         Arg::with_name("recursive")
             .long("recursive")
             .short("r")
@@ -68,12 +83,176 @@ pub fn create_app<'a>() -> App<'a,'a> {
             .takes_value(true)
             .value_name("LEVEL")
             .validator(is_integer)
-            .help("Set max depth in recursive mode"),
+            .help("Set max depth in recursive mode, 'PATH:MIN..MAX' sets it for a single root"),
+        Arg::with_name("min-depth")
+            .requires("recursive")
+            .long("min-depth")
+            .takes_value(true)
+            .value_name("LEVEL")
+            .validator(is_integer)
+            .help("Skip entries shallower than LEVEL in recursive mode, 1 leaves the roots out"),
         Arg::with_name("hidden")
             .requires("recursive")
             .long("hidden")
             .short("x")
             .help("Include hidden files and directories"),
+        Arg::with_name("follow")
+            .requires("recursive")
+            .long("follow")
+            .short("L")
+            .help("Walk into symlinked directories, links leading back into the walk are skipped"),
+        Arg::with_name("one-file-system")
+            .requires("recursive")
+            .long("one-file-system")
+            .short("X")
+            .help("Do not walk into directories on other filesystems, like mount points"),
+        Arg::with_name("threads")
+            .requires("recursive")
+            .long("threads")
+            .short("j")
+            .takes_value(true)
+            .value_name("COUNT")
+            .validator(is_integer)
+            .help("Walk directories with COUNT threads in recursive mode, 0 for one per core"),
+        Arg::with_name("symlink-mode")
+            .long("symlink-mode")
+            .takes_value(true)
+            .value_name("MODE")
+            .possible_values(&["link", "target", "both"])
+            .default_value("link")
+            .help("Rename the symlink itself, the path it points to or both, links are updated to stay valid"),
+        Arg::with_name("sort")
+            .long("sort")
+            .takes_value(true)
+            .value_name("ORDER")
+            .possible_values(&["name", "natural", "mtime", "size", "none"])
+            .default_value("natural")
+            .help("Order of the selected paths for counters and the editor, renames and dumps keep the order that avoids conflicts"),
+        Arg::with_name("reverse")
+            .long("reverse")
+            .help("Reverse the order of the selected paths"),
+        Arg::with_name("no-ignore")
+            .requires("recursive")
+            .long("no-ignore")
+            .help("Do not skip paths listed in .gitignore, .ignore and .rxignore files"),
+        Arg::with_name("include")
+            .requires("recursive")
+            .long("include")
+            .takes_value(true)
+            .value_name("GLOB")
+            .multiple(true)
+            .number_of_values(1)
+            .help("Only rename paths matching GLOB, e.g. '*.jpg'"),
+        Arg::with_name("exclude")
+            .requires("recursive")
+            .long("exclude")
+            .takes_value(true)
+            .value_name("GLOB")
+            .multiple(true)
+            .number_of_values(1)
+            .help("Skip paths matching GLOB, excluded directories are not walked, e.g. 'node_modules/**'"),
+        Arg::with_name("where")
+            .requires("recursive")
+            .long("where")
+            .takes_value(true)
+            .value_name("REGEX")
+            .help("Only rename paths whose path relative to the root matches REGEX"),
+        Arg::with_name("type")
+            .long("type")
+            .takes_value(true)
+            .value_name("TYPE")
+            .possible_values(&["f", "d", "l"])
+            .multiple(true)
+            .number_of_values(1)
+            .help("Only rename entries of TYPE: f for files, d for directories, l for symlinks, followed links count as their target"),
+        Arg::with_name("size")
+            .long("size")
+            .takes_value(true)
+            .value_name("SIZE")
+            .allow_hyphen_values(true)
+            .validator(is_size)
+            .multiple(true)
+            .number_of_values(1)
+            .help("Only rename files of SIZE, '+10M' at least, '-1Ki' at most, units are k, M, G, T and Ki, Mi, Gi, Ti"),
+        Arg::with_name("newer")
+            .long("newer")
+            .takes_value(true)
+            .value_name("WHEN")
+            .validator(is_time)
+            .help("Only rename paths modified after WHEN: a date '2023-01-01', a duration '2d' or a file"),
+        Arg::with_name("older")
+            .long("older")
+            .takes_value(true)
+            .value_name("WHEN")
+            .validator(is_time)
+            .help("Only rename paths modified before WHEN: a date '2023-01-01', a duration '2d' or a file"),
+        Arg::with_name("empty")
+            .long("empty")
+            .help("Only rename empty files and directories"),
+        Arg::with_name("null")
+            .long("null")
+            .short("0")
+            .help("Paths read from stdin are separated by NUL instead of newline"),
+    ];
+
+    let tidy_args = [
+        Arg::with_name("separator")
+            .long("separator")
+            .takes_value(true)
+            .value_name("SEPARATOR")
+            .possible_values(&["space", "_", "-", "."])
+            .help("Convert every separator to this one"),
+        Arg::with_name("split-camel")
+            .long("split-camel")
+            .help("Split camelCase words"),
+    ];
+
+    //applied in the order they are given
+    let position_args = [
+        Arg::with_name("insert")
+            .long("insert")
+            .takes_value(true)
+            .value_name("N:TEXT")
+            .multiple(true)
+            .number_of_values(1)
+            .allow_hyphen_values(true)
+            .validator(is_insertion)
+            .help("Insert TEXT after the first N characters"),
+        Arg::with_name("remove")
+            .long("remove")
+            .takes_value(true)
+            .value_name("FROM-TO")
+            .multiple(true)
+            .number_of_values(1)
+            .validator(is_char_range)
+            .help("Remove characters FROM to TO, counted from 1, e.g. 3-7"),
+        Arg::with_name("prepend")
+            .long("prepend")
+            .takes_value(true)
+            .value_name("TEXT")
+            .multiple(true)
+            .number_of_values(1)
+            .allow_hyphen_values(true)
+            .help("Add TEXT at the beginning"),
+        Arg::with_name("append")
+            .long("append")
+            .takes_value(true)
+            .value_name("TEXT")
+            .multiple(true)
+            .number_of_values(1)
+            .allow_hyphen_values(true)
+            .help("Add TEXT at the end, before the extension"),
+        Arg::with_name("strip")
+            .long("strip")
+            .takes_value(true)
+            .value_name("N")
+            .multiple(true)
+            .number_of_values(1)
+            .validator(is_integer)
+            .help("Remove the first N characters"),
+        Arg::with_name("whole-name")
+            .long("whole-name")
+            .help("Count positions in the whole name instead of the name without extension"),
     ];
 
 
@@ -106,6 +285,13 @@ pub fn create_app<'a>() -> App<'a,'a> {
                 .validator(is_integer)
                 .help("Limit of replacements, all matches if set to 0"),
         )
+        .arg(
+            Arg::with_name("tidy")
+                .long("tidy")
+                .help("Clean up separators after the replacement"),
+        )
+        .args(&tidy_args.iter().cloned().map(|arg| arg.requires("tidy")).collect::<Vec<_>>())
+        .args(&position_args)
         .args(&common_args)
         .args(&path_args)
         .subcommand(
@@ -131,8 +317,244 @@ pub fn create_app<'a>() -> App<'a,'a> {
             SubCommand::with_name(TO_ASCII_SUBCOMMMAND)
                 .args(&common_args)
                 .args(&path_args)
+                .arg(
+                    Arg::with_name("locale")
+                        .long("locale")
+                        .takes_value(true)
+                        .value_name("LOCALE")
+                        .possible_values(&LOCALES)
+                        .help("Use the transliteration conventions of a language, e.g. 'ä' -> 'ae' for de"),
+                )
+                .arg(
+                    Arg::with_name("map")
+                        .long("map")
+                        .takes_value(true)
+                        .value_name("FILE")
+                        .validator_os(is_valid_string)
+                        .help("File with one 'char=replacement' per line, overrides the locale"),
+                )
+                .arg(
+                    Arg::with_name("keep")
+                        .long("keep")
+                        .takes_value(true)
+                        .value_name("CLASS")
+                        .validator_os(is_valid_string)
+                        .help("Characters to keep as they are, e.g. '£€' or '\\p{Emoji_Presentation}'"),
+                )
                 .about("Replace file name UTF-8 chars with ASCII chars representation."),
         )
+        .subcommand(
+            SubCommand::with_name(EDIT_SUBCOMMAND)
+                .args(&common_args)
+                .args(&path_args)
+                .about("Rename paths by editing them in $EDITOR"),
+        )
+        .subcommand(
+            SubCommand::with_name(FROM_CSV_SUBCOMMAND)
+                .args(&common_args)
+                .arg(
+                    Arg::with_name("MAPFILE")
+                        .takes_value(true)
+                        .required(true)
+                        .value_name("MAPFILE")
+                        .validator_os(is_valid_string)
+                        .index(1),
+                )
+                .arg(
+                    Arg::with_name("base")
+                        .long("base")
+                        .takes_value(true)
+                        .value_name("DIR")
+                        .default_value(".")
+                        .validator_os(is_valid_string)
+                        .help("Directory the mapping paths are relative to"),
+                )
+                .arg(
+                    Arg::with_name("delimiter")
+                        .long("delimiter")
+                        .takes_value(true)
+                        .value_name("CHAR")
+                        .validator(is_single_byte)
+                        .help("Column delimiter, tab for .tsv files and comma otherwise"),
+                )
+                .arg(
+                    Arg::with_name("header")
+                        .long("header")
+                        .help("Skip the first row of the mapping file"),
+                )
+                .about("Read \"old name, new name\" rows from a CSV/TSV file"),
+        )
+        .subcommand(
+            SubCommand::with_name(TEMPLATE_SUBCOMMAND)
+                .args(&common_args)
+                .arg(
+                    Arg::with_name("TEMPLATE")
+                        .help("New name template, e.g. '{artist|Unknown}/{track:02} {title}'")
+                        .required(true)
+                        .validator_os(is_valid_string)
+                        .index(1),
+                )
+                .arg(
+                    Arg::with_name("match")
+                        .long("match")
+                        .short("m")
+                        .takes_value(true)
+                        .value_name("EXPRESSION")
+                        .default_value(".*")
+                        .validator_os(is_valid_string)
+                        .help("Only rename names matching the regex, its captures are template variables"),
+                )
+                .arg(
+                    Arg::with_name("seed")
+                        .long("seed")
+                        .takes_value(true)
+                        .value_name("SEED")
+                        .validator(is_integer)
+                        .help("Seed for {uuid}, {ulid} and {rand}, so dry-run and force give the same names"),
+                )
+                .args(&path_args)
+                .about("Build new names from a template with captures, metadata and counters"),
+        )
+        .subcommand(
+            SubCommand::with_name(URL_DECODE_SUBCOMMAND)
+                .args(&common_args)
+                .args(&path_args)
+                .about("Decode percent-encoded file names, e.g. 'My%20Report.pdf'"),
+        )
+        .subcommand(
+            SubCommand::with_name(URL_ENCODE_SUBCOMMAND)
+                .args(&common_args)
+                .args(&path_args)
+                .about("Percent-encode file names for object stores and URLs"),
+        )
+        .subcommand(
+            SubCommand::with_name(TIDY_SUBCOMMAND)
+                .args(&common_args)
+                .args(&tidy_args)
+                .args(&path_args)
+                .about("Collapse repeated separators and trim them around names, extensions of up to 5 letters or digits and the leading dot of hidden files are kept"),
+        )
+        .subcommand(
+            SubCommand::with_name(MEDIA_SUBCOMMAND)
+                .args(&common_args)
+                .arg(
+                    Arg::with_name("template")
+                        .long("template")
+                        .short("t")
+                        .takes_value(true)
+                        .value_name("TEMPLATE")
+                        .default_value("{show}{?season: - S{season:02}E{episode:02}}{?year: ({year})}.{ext}")
+                        .validator_os(is_valid_string)
+                        .help("New name template with {show}, {season}, {episode}, {year} and {resolution}"),
+                )
+                .arg(
+                    Arg::with_name("seed")
+                        .long("seed")
+                        .takes_value(true)
+                        .value_name("SEED")
+                        .validator(is_integer)
+                        .help("Seed for {uuid}, {ulid} and {rand}, so dry-run and force give the same names"),
+                )
+                .args(&path_args)
+                .about("Rename TV episodes and movies from their release names"),
+        )
+        .subcommand(
+            SubCommand::with_name(DATES_SUBCOMMAND)
+                .args(&common_args)
+                .arg(
+                    Arg::with_name("order")
+                        .long("order")
+                        .takes_value(true)
+                        .value_name("ORDER")
+                        .possible_values(&["day-first", "month-first"])
+                        .help("How to read dates like 03-04-2024, they are reported as ambiguous otherwise"),
+                )
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .takes_value(true)
+                        .value_name("FORMAT")
+                        .default_value("%Y-%m-%d")
+                        .validator(is_date_format)
+                        .help("Target date format, strftime syntax"),
+                )
+                .args(&path_args)
+                .about("Find dates in names and rewrite them to one format"),
+        )
+        .subcommand(
+            SubCommand::with_name(SEQUENCE_SUBCOMMAND)
+                .args(&common_args)
+                .arg(
+                    Arg::with_name("start")
+                        .long("start")
+                        .takes_value(true)
+                        .value_name("FRAME")
+                        .validator(is_integer)
+                        .help("First frame of each sequence, e.g. 1001"),
+                )
+                .arg(
+                    Arg::with_name("offset")
+                        .long("offset")
+                        .takes_value(true)
+                        .value_name("FRAMES")
+                        .allow_hyphen_values(true)
+                        .validator(is_signed_integer)
+                        .conflicts_with("start")
+                        .help("Add FRAMES to every frame number, can be negative"),
+                )
+                .arg(
+                    Arg::with_name("compact")
+                        .long("compact")
+                        .conflicts_with("scale")
+                        .help("Remove the gaps between frames"),
+                )
+                .arg(
+                    Arg::with_name("step")
+                        .long("step")
+                        .takes_value(true)
+                        .value_name("FRAMES")
+                        .validator(is_integer)
+                        .requires("compact")
+                        .help("Distance between compacted frames [default: 1]"),
+                )
+                .arg(
+                    Arg::with_name("scale")
+                        .long("scale")
+                        .takes_value(true)
+                        .value_name("RATIO")
+                        .validator(is_ratio)
+                        .help("Multiply the distance to the first frame by RATIO, e.g. 2 or 1/2"),
+                )
+                .arg(
+                    Arg::with_name("padding")
+                        .long("padding")
+                        .takes_value(true)
+                        .value_name("WIDTH")
+                        .validator(is_integer)
+                        .help("Zero pad frame numbers to WIDTH digits instead of keeping the detected padding"),
+                )
+                .group(
+                    ArgGroup::with_name("renumber")
+                        .args(&["start", "offset", "compact", "scale", "padding"])
+                        .multiple(true)
+                        .required(true),
+                )
+                .args(&path_args)
+                .about("Renumber frame sequences by prefix, padding and suffix"),
+        )
+        .subcommand(
+            SubCommand::with_name(POSITION_SUBCOMMAND)
+                .args(&common_args)
+                .args(&position_args)
+                .group(
+                    ArgGroup::with_name("edits")
+                        .args(&["insert", "remove", "prepend", "append", "strip"])
+                        .multiple(true)
+                        .required(true),
+                )
+                .args(&path_args)
+                .about("Insert, remove, prepend or append characters at fixed positions"),
+        )
 }
 
 
@@ -150,6 +572,146 @@ fn is_integer(arg: String) -> Result<(), String>{
 
 
 
+fn is_size(arg: String) -> Result<(), String> {
+    match parse_size(&arg) {
+        Some(_) => Ok(()), 
+        None => Err("Expected a size like '+10M' or '-1Ki'".to_string()),
+    }
+}
+
+
+
+fn is_time(arg: String) -> Result<(), String> {
+    match parse_time(&arg, SystemTime::now()) {
+        Some(_) => Ok(()), 
+        None => Err("Expected a date 'YYYY-MM-DD[ HH:MM:SS]', a duration like '2d' or an existing file".to_string()),
+    }
+}
+
+
+
+//accept negative integers
+fn is_signed_integer(arg: String) -> Result<(), String> {
+    match arg.parse::<i64>() {
+        Ok(_) => Ok(()), 
+        Err(_) => Err("Value provided is not an integer".to_string()),
+    }
+}
+
+
+
+/** split "src:1..3", "src:..2" or "src:1.." into the root and its depth bounds */
+pub fn parse_depth_root(arg: &str) -> Option<(&str, Option<usize>, Option<usize>)> {
+    let (path, range) = arg.rsplit_once(':')?; 
+    let (min, max) = range.split_once("..")?; 
+    let bound = |value: &str| if value.is_empty() { Some(None) } else { value.parse::<usize>().ok().map(Some) }; 
+    let (min, max) = (bound(min)?, bound(max)?); 
+
+    if path.is_empty() || min.zip(max).map(|(min, max)| max < min).unwrap_or(false) {
+        return None; 
+    }
+
+    Some((path, min, max))
+}
+
+
+
+/** parse "3:TEXT" as a position and the text to insert */
+pub fn parse_insertion(arg: &str) -> Option<(usize, &str)> {
+    let mut parts = arg.splitn(2, ':'); 
+    let position = parts.next()?.parse::<usize>().ok()?; 
+
+    Some((position, parts.next()?))
+}
+
+
+fn is_insertion(arg: String) -> Result<(), String> {
+    match parse_insertion(&arg) {
+        Some(_) => Ok(()), 
+        None => Err("Value provided is not like N:TEXT".to_string()),
+    }
+}
+
+
+
+/** parse "3-7" or "3" as an inclusive range of characters counted from 1 */
+pub fn parse_char_range(arg: &str) -> Option<(usize, usize)> {
+    let mut parts = arg.splitn(2, '-'); 
+    let from = parts.next()?.trim().parse::<usize>().ok()?; 
+    let to = match parts.next() {
+        Some(to) => to.trim().parse::<usize>().ok()?, 
+        None => from, 
+    }; 
+
+    if from == 0 || to < from {
+        return None; 
+    }
+
+    Some((from, to))
+}
+
+
+fn is_char_range(arg: String) -> Result<(), String> {
+    match parse_char_range(&arg) {
+        Some(_) => Ok(()), 
+        None => Err("Value provided is not a range like 3-7".to_string()),
+    }
+}
+
+
+
+/** parse "2" or "1/2" as a positive ratio */
+pub fn parse_ratio(arg: &str) -> Option<(u64, u64)> {
+    let mut parts = arg.splitn(2, '/'); 
+    let numerator = parts.next()?.trim().parse::<u64>().ok()?; 
+    let denominator = match parts.next() {
+        Some(denominator) => denominator.trim().parse::<u64>().ok()?, 
+        None => 1, 
+    }; 
+
+    if numerator == 0 || denominator == 0 {
+        return None; 
+    }
+
+    Some((numerator, denominator))
+}
+
+
+fn is_ratio(arg: String) -> Result<(), String> {
+    match parse_ratio(&arg) {
+        Some(_) => Ok(()), 
+        None => Err("Value provided is not a ratio like 2 or 1/2".to_string()),
+    }
+}
+
+
+
+//strftime format that can be part of a file name
+fn is_date_format(arg: String) -> Result<(), String> {
+    if arg.contains('/') {
+        return Err("Date format cannot contain '/'".to_string()); 
+    }
+
+    if StrftimeItems::new(&arg).any(|item| item == Item::Error) {
+        return Err("Value provided is not a valid date format".to_string()); 
+    }
+
+    Ok(())
+}
+
+
+
+//accept a single ASCII character, or "\t" for tabs
+fn is_single_byte(arg: String) -> Result<(), String> {
+    if arg == "\\t" || (arg.len() == 1 && arg.is_ascii()) {
+        Ok(())
+    } else {
+        Err("Value provided is not a single ASCII character".to_string())
+    }
+}
+
+
+
 /* a borrowed reference to the OS string  */
 fn is_valid_string(os_str: &OsStr) -> Result<(), OsString> {
     match os_str.to_str() {
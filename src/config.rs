@@ -1,10 +1,24 @@
+use std::path::Path;
 use std::sync::Arc;
+use std::thread;
+use std::time::SystemTime;
 
 use clap::ArgMatches;
 use regex::Regex;
 
-use crate::app::{ create_app, FROM_FILE_SUBCOMMAND, TO_ASCII_SUBCOMMMAND}; 
+use crate::app::{ create_app, parse_char_range, parse_depth_root, parse_insertion, parse_ratio, DATES_SUBCOMMAND, EDIT_SUBCOMMAND, FROM_CSV_SUBCOMMAND, FROM_FILE_SUBCOMMAND, MEDIA_SUBCOMMAND, POSITION_SUBCOMMAND, SEQUENCE_SUBCOMMAND, TEMPLATE_SUBCOMMAND, TIDY_SUBCOMMAND, TO_ASCII_SUBCOMMMAND, URL_DECODE_SUBCOMMAND, URL_ENCODE_SUBCOMMAND}; 
+use crate::dates::{DateFormatter, DateOrder}; 
+use crate::documents::{OfficeProvider, PdfProvider}; 
+use crate::filter::{parse_size, parse_time, EntryType, PathFilter, Predicates}; 
+use crate::image::ImageProvider; 
+use crate::media::{MediaParser, MediaProvider}; 
 use crate::output::Printer; 
+use crate::providers::{CaptureProvider, CounterProvider, MetadataProvider, PathProvider, RandomProvider}; 
+use crate::renamer::{Positional, Tidy};
+use crate::sequence::{Renumber, Spacing}; 
+use crate::template::{Provider, Template}; 
+use crate::translit::Transliterator; 
+use crate::video::VideoProvider; 
 
 
 
@@ -13,6 +27,9 @@ pub struct Config {
     pub backup: bool, 
     pub dirs: bool, 
     pub dump: bool, 
+    pub symlink_mode: SymlinkMode, 
+    pub sort: SortOrder, 
+    pub reverse: bool, 
     pub run_mode: RunMode, 
     pub replace_mode:ReplaceMode, 
     pub printer: Printer
@@ -24,19 +41,68 @@ pub struct Config {
 pub enum RunMode {
     Simple(Vec<String>), 
     Recursive{
-        paths: Vec<String>,
-        max_depth: Option<usize>,
-        hidden: bool  
+        roots: Vec<WalkRoot>,
+        hidden: bool, 
+        follow: bool, 
+        one_file_system: bool, 
+        filter: PathFilter, 
+        predicates: Predicates, 
+        ignore: bool, 
+        threads: usize
     }, 
 
     FromFile {
         path: String, 
         undo: bool
+    },
+
+    Stdin {
+        null: bool, 
+        predicates: Predicates
+    },
+
+    FromCsv {
+        path: String, 
+        base: String, 
+        delimiter: Option<u8>, 
+        header: bool
     }
 }
 
 
 
+/** what is renamed when a listed path is a symlink */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SymlinkMode {
+    Link, 
+    Target, 
+    Both
+}
+
+
+
+/** order of the selected paths */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SortOrder {
+    None, 
+    Name, 
+    Natural, 
+    Mtime, 
+    Size
+}
+
+
+
+/** a recursive mode root and the depths of the entries listed under it, the root itself is at 0 */
+#[derive(Debug)]
+pub struct WalkRoot {
+    pub path: String, 
+    pub min_depth: usize, 
+    pub max_depth: Option<usize>
+}
+
+
+
 pub enum ReplaceMode {
     RegExp {
         expression: Regex, 
@@ -44,7 +110,35 @@ pub enum ReplaceMode {
         limit: usize
     },
 
-    ToASCII
+    ToASCII(Transliterator),
+
+    Editor,
+
+    Template {
+        expression: Regex, 
+        template: Template
+    },
+
+    UrlDecode,
+
+    UrlEncode,
+
+    Tidy(Tidy),
+
+    Media {
+        parser: MediaParser, 
+        template: Template
+    },
+
+    Dates(DateFormatter),
+    Sequence(Renumber),
+    Positional {
+        edit: Positional, 
+        whole_name: bool
+    },
+
+    //replace modes applied one after the other
+    Pipeline(Vec<ReplaceMode>)
 }
 
 
@@ -55,7 +149,17 @@ pub enum ReplaceMode {
 pub enum AppCommand {
     Root,
     FromFile, 
-    ToASCII
+    ToASCII,
+    Edit,
+    FromCsv,
+    Template,
+    UrlDecode,
+    UrlEncode,
+    Tidy,
+    Media,
+    Dates,
+    Sequence,
+    Position
 }
 
 
@@ -66,6 +170,16 @@ impl AppCommand {
             "" => Ok(AppCommand::Root), 
             FROM_FILE_SUBCOMMAND => Ok(AppCommand::FromFile), 
             TO_ASCII_SUBCOMMMAND => Ok(AppCommand::ToASCII),
+            EDIT_SUBCOMMAND => Ok(AppCommand::Edit),
+            FROM_CSV_SUBCOMMAND => Ok(AppCommand::FromCsv),
+            TEMPLATE_SUBCOMMAND => Ok(AppCommand::Template),
+            URL_DECODE_SUBCOMMAND => Ok(AppCommand::UrlDecode),
+            URL_ENCODE_SUBCOMMAND => Ok(AppCommand::UrlEncode),
+            TIDY_SUBCOMMAND => Ok(AppCommand::Tidy),
+            MEDIA_SUBCOMMAND => Ok(AppCommand::Media),
+            DATES_SUBCOMMAND => Ok(AppCommand::Dates),
+            SEQUENCE_SUBCOMMAND => Ok(AppCommand::Sequence),
+            POSITION_SUBCOMMAND => Ok(AppCommand::Position),
             _  => Err(format!("Non-registred subcommand '{}'", name)), 
             
         }
@@ -107,6 +221,20 @@ impl ArguementParser<'_> {
             }); 
         }
 
+        if let AppCommand::FromCsv = self.command {
+            let delimiter = self.matches.value_of("delimiter").map(|d| match d {
+                "\\t" => b'\t', 
+                _ => d.as_bytes()[0]
+            }); 
+
+            return Ok(RunMode::FromCsv { 
+                path: String::from(self.matches.value_of("MAPFILE").unwrap_or_default()), 
+                base: String::from(self.matches.value_of("base").unwrap_or(".")), 
+                delimiter, 
+                header: self.matches.is_present("header")
+            }); 
+        }
+
 
         //let detect runt 
         let input_paths:Vec<String> = self.matches
@@ -117,27 +245,85 @@ impl ArguementParser<'_> {
 
         
 
+        let read_stdin = input_paths.iter().any(|p| p == "-"); 
+        if read_stdin || self.matches.is_present("null") {
+            if input_paths.len() > 1 || !read_stdin {
+                return Err(format!(
+                    "{} '-' must be the only path when reading from stdin", 
+                    self.printer.colors.error.paint("Error: ")
+                )); 
+            }
+
+            if self.matches.is_present("recursive") {
+                return Err(format!(
+                    "{} '-' cannot be used in recursive mode", 
+                    self.printer.colors.error.paint("Error: ")
+                )); 
+            }
+
+            return Ok(RunMode::Stdin { 
+                null: self.matches.is_present("null"), 
+                predicates: self.parse_predicates() 
+            }); 
+        }
+
         if self.matches.is_present("recursive") {
-            let max_depth = if self.matches.is_present("max-depth") {
-                Some(
-                    self.matches
-                        .value_of("max-depth")
-                        .unwrap_or_default()
-                        .parse::<usize>()
-                        .unwrap_or_default(), 
-                )
-
-            } else {
-                None
+            let number = |name: &str| self.matches.value_of(name).and_then(|v| v.parse::<usize>().ok()); 
+            let (min_depth, max_depth) = (number("min-depth").unwrap_or(0), number("max-depth")); 
+
+            //"PATH:MIN..MAX" overrides the depths of a root, unless such a path exists
+            let mut roots = Vec::new(); 
+            for path in input_paths {
+                let root = match parse_depth_root(&path) {
+                    Some((root, min, max)) if !Path::new(&path).exists() => WalkRoot {
+                        path: root.to_string(), 
+                        min_depth: min.unwrap_or(min_depth), 
+                        max_depth: max.or(max_depth)
+                    }, 
+                    _ => WalkRoot { path, min_depth, max_depth }, 
+                }; 
+
+                if root.max_depth.map(|max| max < root.min_depth).unwrap_or(false) {
+                    return Err(format!(
+                        "{} the minimum depth of '{}' is greater than its maximum depth", 
+                        self.printer.colors.error.paint("Error: "), 
+                        root.path
+                    )); 
+                }
+                roots.push(root); 
+            }
+
+            let values = |name: &str| self.matches.values_of(name).map(|v| v.collect::<Vec<&str>>()).unwrap_or_default(); 
+            let filter = match PathFilter::new(&values("include"), &values("exclude"), self.matches.value_of("where")) {
+                Ok(filter) => filter, 
+                Err(err) => return Err(format!("{} {}", self.printer.colors.error.paint("Error: "), err)), 
             }; 
 
             Ok(RunMode::Recursive { 
-                paths: input_paths, 
-                max_depth,
-                hidden: self.matches.is_present("hidden") 
+                roots, 
+                hidden: self.matches.is_present("hidden"), 
+                follow: self.matches.is_present("follow"), 
+                one_file_system: self.matches.is_present("one-file-system"), 
+                filter, 
+                predicates: self.parse_predicates(), 
+                ignore: !self.matches.is_present("no-ignore"), 
+                threads: match number("threads") {
+                    Some(0) => thread::available_parallelism().map(|n| n.get()).unwrap_or(1), 
+                    Some(threads) => threads, 
+                    None => 1, 
+                }
             })
         
         }else {
+            let predicates = ["type", "size", "newer", "older", "empty"].iter().find(|name| self.matches.is_present(name)); 
+            if let Some(predicate) = predicates {
+                return Err(format!(
+                    "{} --{} only applies to paths found in recursive mode or read from stdin", 
+                    self.printer.colors.error.paint("Error: "), 
+                    predicate
+                )); 
+            }
+
             Ok(RunMode::Simple(input_paths))
         }
     }
@@ -145,7 +331,56 @@ impl ArguementParser<'_> {
 
     fn parse_replace_mode(&self) -> Result<ReplaceMode, String> {
         if let AppCommand::ToASCII = self.command {
-            return Ok(ReplaceMode::ToASCII)
+            return match Transliterator::new(
+                self.matches.value_of("locale"), 
+                self.matches.value_of("map").map(Path::new), 
+                self.matches.value_of("keep")
+            ) {
+                Ok(transliterator) => Ok(ReplaceMode::ToASCII(transliterator)), 
+                Err(err) => Err(format!("{} {}", self.printer.colors.error.paint("Error: "), err)), 
+            }; 
+        }
+
+        if let AppCommand::Edit = self.command {
+            return Ok(ReplaceMode::Editor)
+        }
+
+        if let AppCommand::Template = self.command {
+            return self.parse_template(); 
+        }
+
+        if let AppCommand::Media = self.command {
+            let mut providers: Vec<Box<dyn Provider>> = vec![Box::new(MediaProvider::new())]; 
+            providers.append(&mut self.common_providers()); 
+
+            let template = self.compile_template(self.matches.value_of("template").unwrap_or_default(), providers)?; 
+            return Ok(ReplaceMode::Media { parser: MediaParser::new(), template }); 
+        }
+
+        match self.command {
+            AppCommand::UrlDecode => return Ok(ReplaceMode::UrlDecode), 
+            AppCommand::UrlEncode => return Ok(ReplaceMode::UrlEncode), 
+            AppCommand::Tidy => return Ok(ReplaceMode::Tidy(self.parse_tidy())), 
+            AppCommand::Dates => {
+                let order = match self.matches.value_of("order") {
+                    Some("day-first") => Some(DateOrder::DayFirst), 
+                    Some("month-first") => Some(DateOrder::MonthFirst), 
+                    _ => None
+                }; 
+
+                let format = self.matches.value_of("format").unwrap_or("%Y-%m-%d"); 
+                return Ok(ReplaceMode::Dates(DateFormatter::new(order, format))); 
+            }
+            AppCommand::Sequence => return Ok(ReplaceMode::Sequence(self.parse_renumber())), 
+            AppCommand::Position => {
+                let mut edits = self.parse_positional(); 
+                if edits.len() == 1 {
+                    return Ok(edits.remove(0)); 
+                }
+
+                return Ok(ReplaceMode::Pipeline(edits)); 
+            }
+            _ => {}
         }
 
 
@@ -172,8 +407,152 @@ impl ArguementParser<'_> {
             .unwrap_or_default(); 
 
 
-        Ok(ReplaceMode::RegExp { expression, replacement, limit, })
+        let regexp = ReplaceMode::RegExp { expression, replacement, limit, }; 
+        let mut steps = self.parse_positional(); 
+        if self.matches.is_present("tidy") {
+            steps.push(ReplaceMode::Tidy(self.parse_tidy())); 
+        }
+
+        if steps.is_empty() {
+            return Ok(regexp); 
+        }
+
+        steps.insert(0, regexp); 
+        Ok(ReplaceMode::Pipeline(steps))
+
+    }
+
+
+    fn parse_tidy(&self) -> Tidy {
+        let separator = self.matches.value_of("separator").map(|s| match s {
+            "space" => ' ', 
+            _ => s.chars().next().unwrap_or(' ')
+        }); 
+
+        Tidy {
+            separator, 
+            split_camel: self.matches.is_present("split-camel")
+        }
+    }
 
+
+    //positional edits in the order they appear on the command line
+    fn parse_positional(&self) -> Vec<ReplaceMode> {
+        let mut edits: Vec<(usize, Positional)> = Vec::new(); 
+        let mut collect = |name: &str, parse: &dyn Fn(&str) -> Option<Positional>| {
+            if let (Some(values), Some(indices)) = (self.matches.values_of(name), self.matches.indices_of(name)) {
+                edits.extend(indices.zip(values).filter_map(|(index, value)| Some((index, parse(value)?)))); 
+            }
+        }; 
+
+        collect("insert", &|v| parse_insertion(v).map(|(position, text)| Positional::Insert(position, text.to_string()))); 
+        collect("remove", &|v| parse_char_range(v).map(|(from, to)| Positional::Remove(from, to))); 
+        collect("prepend", &|v| Some(Positional::Prepend(v.to_string()))); 
+        collect("append", &|v| Some(Positional::Append(v.to_string()))); 
+        collect("strip", &|v| v.parse::<usize>().ok().map(Positional::Strip)); 
+
+        edits.sort_by_key(|(index, _)| *index); 
+
+        let whole_name = self.matches.is_present("whole-name"); 
+        edits.into_iter().map(|(_, edit)| ReplaceMode::Positional { edit, whole_name }).collect()
+    }
+
+
+    fn parse_renumber(&self) -> Renumber {
+        let number = |name: &str| self.matches.value_of(name).and_then(|v| v.parse::<u64>().ok()); 
+
+        let spacing = if self.matches.is_present("compact") {
+            Spacing::Compact(number("step").unwrap_or(1))
+        } else if let Some((numerator, denominator)) = self.matches.value_of("scale").and_then(parse_ratio) {
+            Spacing::Scale(numerator, denominator)
+        } else {
+            Spacing::Keep
+        }; 
+
+        Renumber {
+            start: number("start"), 
+            offset: self.matches.value_of("offset").and_then(|v| v.parse::<i64>().ok()).unwrap_or(0), 
+            spacing, 
+            padding: number("padding").map(|p| p as usize)
+        }
+    }
+
+
+    //files and symlinks unless --type says otherwise, --include-dirs adds directories
+    fn parse_predicates(&self) -> Predicates {
+        let mut types: Vec<EntryType> = match self.matches.values_of("type") {
+            Some(letters) => letters.filter_map(EntryType::from_letter).collect(),
+            None => vec![EntryType::File, EntryType::Symlink],
+        };
+        if self.matches.is_present("include-dirs") && !types.contains(&EntryType::Directory) {
+            types.push(EntryType::Directory);
+        }
+
+        //every --size narrows the range
+        let mut predicates = Predicates { types, ..Predicates::default() };
+        for (min, max) in self.matches.values_of("size").unwrap_or_default().filter_map(parse_size) {
+            predicates.min_size = predicates.min_size.max(min);
+            predicates.max_size = match (predicates.max_size, max) {
+                (Some(current), Some(max)) => Some(current.min(max)),
+                (current, max) => current.or(max),
+            };
+        }
+
+        let now = SystemTime::now();
+        predicates.newer = self.matches.value_of("newer").and_then(|v| parse_time(v, now));
+        predicates.older = self.matches.value_of("older").and_then(|v| parse_time(v, now));
+        predicates.empty = self.matches.is_present("empty");
+        predicates
+    }
+
+
+    //compile the template once, every variable is resolved against the providers here
+    fn parse_template(&self) -> Result<ReplaceMode, String> {
+        let expression = match Regex::new(self.matches.value_of("match").unwrap_or(".*")) {
+            Ok(expr) => expr, 
+            Err(err) => {
+                return Err(format!(
+                    "{} Bad Expression provided\n\n {}", 
+                    self.printer.colors.error.paint("Error: "), 
+                    self.printer.colors.error.paint(err.to_string()), 
+                ))
+            }
+        };
+
+        let mut providers: Vec<Box<dyn Provider>> = vec![Box::new(CaptureProvider::new(&expression))]; 
+        providers.append(&mut self.common_providers()); 
+
+        let template = self.compile_template(self.matches.value_of("TEMPLATE").unwrap_or_default(), providers)?; 
+        Ok(ReplaceMode::Template { expression, template })
+    }
+
+
+    //variables available to every template
+    fn common_providers(&self) -> Vec<Box<dyn Provider>> {
+        let seed = self.matches.value_of("seed").and_then(|s| s.parse::<u64>().ok()); 
+
+        vec![
+            Box::new(MetadataProvider), 
+            Box::new(CounterProvider), 
+            Box::new(PathProvider), 
+            Box::new(RandomProvider::new(seed)), 
+            Box::new(PdfProvider::default()), 
+            Box::new(OfficeProvider::default()), 
+            Box::new(VideoProvider::default()), 
+            Box::new(ImageProvider::default()), 
+        ]
+    }
+
+
+    fn compile_template(&self, source: &str, providers: Vec<Box<dyn Provider>>) -> Result<Template, String> {
+        match Template::compile(source, providers) {
+            Ok(template) => Ok(template), 
+            Err(err) => Err(format!(
+                "{} Bad template provided\n\n{}", 
+                self.printer.colors.error.paint("Error: "), 
+                self.printer.colors.error.paint(err.pointer(source)), 
+            ))
+        }
     }
 }
 
@@ -232,6 +611,19 @@ fn parse_arguements() -> Result<Config, String> {
         backup: matches.is_present("backup"), 
         dirs: matches.is_present("include-dirs"), 
         dump, 
+        symlink_mode: match matches.value_of("symlink-mode") {
+            Some("target") => SymlinkMode::Target, 
+            Some("both") => SymlinkMode::Both, 
+            _ => SymlinkMode::Link, 
+        }, 
+        sort: match matches.value_of("sort") {
+            Some("none") => SortOrder::None, 
+            Some("name") => SortOrder::Name, 
+            Some("mtime") => SortOrder::Mtime, 
+            Some("size") => SortOrder::Size, 
+            _ => SortOrder::Natural, 
+        }, 
+        reverse: matches.is_present("reverse"), 
         run_mode, 
         replace_mode, 
         printer,
@@ -259,7 +651,8 @@ mod test {
     #[test]
     fn app_command_from_string() {
         assert_eq!(AppCommand::from_str("").unwrap(), AppCommand::Root); //check for empty string  
-        assert_eq!(AppCommand::from_str(FROM_FILE_SUBCOMMAND).unwrap(), AppCommand::FromFile)
+        assert_eq!(AppCommand::from_str(FROM_FILE_SUBCOMMAND).unwrap(), AppCommand::FromFile);
+        assert_eq!(AppCommand::from_str(EDIT_SUBCOMMAND).unwrap(), AppCommand::Edit)
     }
 
 
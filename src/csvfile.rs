@@ -0,0 +1,185 @@
+use crate::error::*;
+use crate::renamer::RenameMap;
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+
+
+/** read a CSV/TSV mapping file with "old name, new name" rows into a rename map */
+pub fn read_rename_map(filepath: &Path, base: &Path, delimiter: Option<u8>, header: bool) -> Result<RenameMap> {
+    let file = match File::open(filepath) {
+        Ok(file) => file,
+        Err(_) => {
+            return Err(Error {
+                kind: ErrorKind::ReadFile,
+                value: Some(filepath.to_string_lossy().to_string()),
+            })
+        }
+    };
+
+    let delimiter = delimiter.unwrap_or_else(|| detect_delimiter(filepath));
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .has_headers(header)
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(file);
+
+    let mut mappings: Vec<(PathBuf, PathBuf)> = Vec::new();
+    for record in reader.records() {
+        let record = match record {
+            Ok(record) => record,
+            Err(err) => {
+                return Err(Error {
+                    kind: ErrorKind::CsvParse,
+                    value: Some(format!("{}\n{}", filepath.display(), err)),
+                })
+            }
+        };
+
+        //skip blank rows left over by spreadsheets
+        if record.iter().all(|field| field.is_empty()) {
+            continue;
+        }
+
+        let line = record.position().map(|p| p.line()).unwrap_or_default();
+        match (record.get(0), record.get(1)) {
+            (Some(source), Some(target)) if !source.is_empty() && !target.is_empty() => {
+                mappings.push((base.join(source), base.join(target)))
+            }
+            _ => {
+                return Err(Error {
+                    kind: ErrorKind::CsvParse,
+                    value: Some(format!("{}:{} expected two non-empty columns", filepath.display(), line)),
+                })
+            }
+        }
+    }
+
+    build_rename_map(mappings)
+}
+
+
+
+//tab separated when the extension says so, comma otherwise
+fn detect_delimiter(filepath: &Path) -> u8 {
+    match filepath.extension().and_then(|e| e.to_str()) {
+        Some(ext) if ext.eq_ignore_ascii_case("tsv") || ext.eq_ignore_ascii_case("tab") => b'\t',
+        _ => b',',
+    }
+}
+
+
+
+/** check every mapping and report all missing sources and duplicate targets at once */
+fn build_rename_map(mappings: Vec<(PathBuf, PathBuf)>) -> Result<RenameMap> {
+    let mut rename_map = RenameMap::new();
+    let mut sources: HashMap<PathBuf, PathBuf> = HashMap::new();
+    let mut missing_sources = String::new();
+    let mut duplicates = String::new();
+
+    for (source, target) in mappings {
+        //keep checking missing sources, so duplicates are reported alongside them
+        if source.symlink_metadata().is_err() {
+            missing_sources.push_str(&format!("\n{}", source.display()));
+        }
+
+        if let Some(old_target) = sources.insert(source.clone(), target.clone()) {
+            duplicates.push_str(&format!(
+                "\n{0}->{1}\n{0}->{2}\n",
+                source.display(),
+                old_target.display(),
+                target.display()
+            ));
+            continue;
+        }
+
+        if source == target {
+            continue;
+        }
+
+        if let Some(old_source) = rename_map.insert(target.clone(), source.clone()) {
+            duplicates.push_str(&format!(
+                "\n{0}->{2}\n{1}->{2}\n",
+                old_source.display(),
+                source.display(),
+                target.display()
+            ));
+        }
+    }
+
+    if missing_sources.is_empty() && duplicates.is_empty() {
+        return Ok(rename_map);
+    }
+
+    let mut error_string = String::new();
+    if !missing_sources.is_empty() {
+        error_string.push_str(&format!("\nMissing sources:{}\n", missing_sources));
+    }
+
+    if !duplicates.is_empty() {
+        error_string.push_str(&format!("\nDuplicated mappings:{}", duplicates));
+    }
+
+    Err(Error {
+        kind: ErrorKind::InvalidMapping,
+        value: Some(error_string),
+    })
+}
+
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs;
+    use std::io::Write;
+
+
+    #[test]
+    fn read_mapping_file() {
+        let tempdir = tempfile::tempdir().expect("Error creating temp directory");
+        let base = tempdir.path();
+
+        for file in &["old_1.txt", "old 2.txt"] {
+            fs::File::create(base.join(file)).expect("Error creating mock file...");
+        }
+
+        let mapfile = base.join("mapping.tsv");
+        fs::File::create(&mapfile)
+            .expect("Error creating mapping file...")
+            .write_all(b"old\tnew\nold_1.txt\tnew_1.txt\n\"old 2.txt\"\tnew 2.txt\n")
+            .expect("Error writing mapping file...");
+
+        let rename_map = read_rename_map(&mapfile, base, None, true).unwrap();
+        assert_eq!(rename_map.len(), 2);
+        assert_eq!(rename_map[&base.join("new_1.txt")], base.join("old_1.txt"));
+        assert_eq!(rename_map[&base.join("new 2.txt")], base.join("old 2.txt"));
+    }
+
+
+    #[test]
+    fn missing_sources_and_duplicates_reported_together() {
+        let tempdir = tempfile::tempdir().expect("Error creating temp directory");
+        let base = tempdir.path();
+
+        for file in &["a.txt", "b.txt"] {
+            fs::File::create(base.join(file)).expect("Error creating mock file...");
+        }
+
+        let mappings = vec![
+            (base.join("a.txt"), base.join("c.txt")),
+            (base.join("b.txt"), base.join("c.txt")),
+            (base.join("missing.txt"), base.join("d.txt")),
+            (base.join("missing.txt"), base.join("e.txt")),
+        ];
+
+        let error = build_rename_map(mappings).unwrap_err();
+        let value = error.value.unwrap();
+        assert!(value.contains("Missing sources:"));
+        assert!(value.contains("missing.txt"));
+        assert!(value.contains("Duplicated mappings:"));
+        assert!(value.contains("e.txt"));
+    }
+}
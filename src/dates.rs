@@ -0,0 +1,191 @@
+//dates embedded in file names
+
+use chrono::NaiveDate;
+use regex::{Captures, Regex};
+
+
+
+const MONTH: &str = "jan(?:uary)?|feb(?:ruary)?|mar(?:ch)?|apr(?:il)?|may|june?|july?|aug(?:ust)?|sep(?:t(?:ember)?)?|oct(?:ober)?|nov(?:ember)?|dec(?:ember)?";
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DateOrder {
+    DayFirst,
+    MonthFirst,
+}
+
+
+
+pub struct DateFormatter {
+    order: Option<DateOrder>,
+    format: String,
+    pattern: Regex,
+}
+
+
+impl DateFormatter {
+    pub fn new(order: Option<DateOrder>, format: &str) -> DateFormatter {
+        let pattern = format!(
+            r"(?ix)
+            (?P<a_y>\d{{4}})[-./_\ ](?P<a_m>\d{{1,2}})[-./_\ ](?P<a_d>\d{{1,2}})
+            | (?P<b_y>\d{{4}})(?P<b_m>\d{{2}})(?P<b_d>\d{{2}})
+            | (?P<c_1>\d{{1,2}})[-./\ ](?P<c_2>\d{{1,2}})[-./\ ](?P<c_y>\d{{4}})
+            | (?P<d_d>\d{{1,2}})(?:st|nd|rd|th)?[-.\ ]?(?P<d_m>{month})\.?[-.,\ ]*(?P<d_y>\d{{4}})
+            | (?P<e_m>{month})\.?[-.\ ]?(?P<e_d>\d{{1,2}})(?:st|nd|rd|th)?,?[-.\ ]?(?P<e_y>\d{{4}})
+            ",
+            month = MONTH
+        );
+
+        DateFormatter {
+            order,
+            format: String::from(format),
+            pattern: Regex::new(&pattern).unwrap(),
+        }
+    }
+
+
+    /** rewrite every date found, None when there is none and an error for ambiguous dates */
+    pub fn apply(&self, name: &str) -> Result<Option<String>, String> {
+        let mut rewritten = String::new();
+        let mut position = 0;
+        let mut copied = 0;
+        let mut found = false;
+
+        while let Some(captures) = self.pattern.captures(&name[position..]) {
+            let found_match = captures.get(0).unwrap();
+            let (start, end) = (position + found_match.start(), position + found_match.end());
+
+            let date = if is_delimited(name, start, end) { self.parse(&captures)? } else { None };
+
+            match date {
+                Some(date) => {
+                    rewritten.push_str(&name[copied..start]);
+                    rewritten.push_str(&date.format(&self.format).to_string());
+                    copied = end;
+                    position = end;
+                    found = true;
+                }
+                //not a date, try again from the next char
+                None => position = start + name[start..].chars().next().map(char::len_utf8).unwrap_or(1),
+            }
+        }
+
+        if !found {
+            return Ok(None);
+        }
+
+        rewritten.push_str(&name[copied..]);
+        Ok(Some(rewritten))
+    }
+
+
+    fn parse(&self, captures: &Captures) -> Result<Option<NaiveDate>, String> {
+        let number = |name: &str| captures.name(name).and_then(|m| m.as_str().parse::<u32>().ok());
+
+        let (year, month, day) = if let Some(year) = number("a_y") {
+            (year, number("a_m"), number("a_d"))
+        } else if let Some(year) = number("b_y") {
+            (year, number("b_m"), number("b_d"))
+        } else if let Some(year) = number("c_y") {
+            let (first, second) = (number("c_1").unwrap_or_default(), number("c_2").unwrap_or_default());
+            match self.resolve_order(first, second) {
+                Some(DateOrder::DayFirst) => (year, Some(second), Some(first)),
+                Some(DateOrder::MonthFirst) => (year, Some(first), Some(second)),
+                None => {
+                    return Err(format!(
+                        "ambiguous date '{}', use --order to pick day or month first",
+                        captures.get(0).unwrap().as_str()
+                    ))
+                }
+            }
+        } else if let Some(year) = number("d_y") {
+            (year, month_number(&captures["d_m"]), number("d_d"))
+        } else {
+            (number("e_y").unwrap_or_default(), month_number(&captures["e_m"]), number("e_d"))
+        };
+
+        Ok(match (month, day) {
+            (Some(month), Some(day)) => NaiveDate::from_ymd_opt(year as i32, month, day),
+            _ => None,
+        })
+    }
+
+
+    //only guess when a number cannot be a month, or both readings give the same date
+    fn resolve_order(&self, first: u32, second: u32) -> Option<DateOrder> {
+        if first > 12 || first == second {
+            Some(DateOrder::DayFirst)
+        } else if second > 12 {
+            Some(DateOrder::MonthFirst)
+        } else {
+            self.order
+        }
+    }
+}
+
+
+
+fn month_number(name: &str) -> Option<u32> {
+    let months = ["jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec"];
+    let prefix = name.get(..3)?.to_lowercase();
+
+    months.iter().position(|m| *m == prefix).map(|index| index as u32 + 1)
+}
+
+
+//"IMG_20240131" is a date, "12320240131" is not
+fn is_delimited(name: &str, start: usize, end: usize) -> bool {
+    let before = name[..start].chars().next_back();
+    let after = name[end..].chars().next();
+    let first = name[start..].chars().next();
+    let last = name[..end].chars().next_back();
+
+    let touches = |outside: Option<char>, inside: Option<char>| match (outside, inside) {
+        (Some(o), Some(i)) => (o.is_ascii_digit() && i.is_ascii_digit()) || (o.is_alphabetic() && i.is_alphabetic()),
+        _ => false,
+    };
+
+    !touches(before, first) && !touches(after, last)
+}
+
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+
+    #[test]
+    fn rewrite_date_formats() {
+        let formatter = DateFormatter::new(None, "%Y-%m-%d");
+
+        assert_eq!(formatter.apply("IMG_20240131_1230.jpg").unwrap().unwrap(), "IMG_2024-01-31_1230.jpg");
+        assert_eq!(formatter.apply("report 31-01-2024.pdf").unwrap().unwrap(), "report 2024-01-31.pdf");
+        assert_eq!(formatter.apply("Jan 31 2024 notes.txt").unwrap().unwrap(), "2024-01-31 notes.txt");
+        assert_eq!(formatter.apply("3rd March, 2023 - 2023.12.01").unwrap().unwrap(), "2023-03-03 - 2023-12-01");
+        assert_eq!(formatter.apply("scan 12-31-2024").unwrap().unwrap(), "scan 2024-12-31");
+    }
+
+
+    #[test]
+    fn skip_invalid_dates() {
+        let formatter = DateFormatter::new(None, "%Y-%m-%d");
+
+        assert_eq!(formatter.apply("invoice 20241399.pdf").unwrap(), None);
+        assert_eq!(formatter.apply("id 1202401310.txt").unwrap(), None);
+        assert_eq!(formatter.apply("plain name.txt").unwrap(), None);
+    }
+
+
+    #[test]
+    fn ambiguous_dates() {
+        let formatter = DateFormatter::new(None, "%Y-%m-%d");
+        assert!(formatter.apply("03-04-2024.txt").unwrap_err().contains("ambiguous date '03-04-2024'"));
+
+        let day_first = DateFormatter::new(Some(DateOrder::DayFirst), "%d %b %Y");
+        assert_eq!(day_first.apply("03-04-2024.txt").unwrap().unwrap(), "03 Apr 2024.txt");
+
+        let month_first = DateFormatter::new(Some(DateOrder::MonthFirst), "%Y%m%d");
+        assert_eq!(month_first.apply("03-04-2024.txt").unwrap().unwrap(), "20240304.txt");
+    }
+}
@@ -0,0 +1,653 @@
+//PDF and office document metadata
+//
+// {pdf:Title}          entry of the PDF info dictionary, XMP when it is missing
+// {pdf:CreationDate}   dates take an optional format, e.g. {pdf:CreationDate:%Y}
+// {doc:title}          docProps/core.xml of OOXML files, meta.xml of ODF files
+
+use crate::providers::{check_key, split_key, FileCache};
+use crate::template::{Context, Provider};
+use chrono::{NaiveDate, NaiveDateTime};
+use flate2::read::ZlibDecoder;
+use regex::bytes::{Captures, Regex};
+use std::borrow::Cow;
+use std::cell::OnceCell;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::Path;
+
+
+
+type Fields = HashMap<String, String>;
+
+
+const PDF_KEYS: [&str; 8] = ["Title", "Author", "Subject", "Keywords", "Creator", "Producer", "CreationDate", "ModDate"];
+const PDF_DATES: [&str; 2] = ["CreationDate", "ModDate"];
+
+//info dictionary key -> XMP properties holding the same value
+const XMP_KEYS: [(&str, &[&str]); 8] = [
+    ("Title", &["dc:title"]),
+    ("Author", &["dc:creator"]),
+    ("Subject", &["dc:description"]),
+    ("Keywords", &["pdf:Keywords"]),
+    ("Creator", &["xmp:CreatorTool"]),
+    ("Producer", &["pdf:Producer"]),
+    ("CreationDate", &["xmp:CreateDate"]),
+    ("ModDate", &["xmp:ModifyDate"]),
+];
+
+//{doc:key} -> core.xml (OOXML) and meta.xml (ODF) elements, first one found wins
+const DOC_KEYS: [(&str, &[&str]); 8] = [
+    ("title", &["dc:title"]),
+    ("subject", &["dc:subject"]),
+    ("author", &["meta:initial-creator", "dc:creator"]),
+    ("keywords", &["cp:keywords", "meta:keyword"]),
+    ("description", &["dc:description"]),
+    ("lastmodifiedby", &["cp:lastModifiedBy", "dc:creator"]),
+    ("created", &["dcterms:created", "meta:creation-date"]),
+    ("modified", &["dcterms:modified", "dc:date"]),
+];
+const DOC_DATES: [&str; 2] = ["created", "modified"];
+
+
+lazy_static! {
+    static ref PDF_INFO: Regex = Regex::new(r"/Info\s*(\d+)\s+(\d+)\s+R").unwrap();
+    static ref PDF_OBJECT: Regex = Regex::new(r"(?:^|[^0-9])(\d+)\s+(\d+)\s+obj\b").unwrap();
+    static ref PDF_REFERENCE: Regex = Regex::new(r"^(\d+)\s+(\d+)\s+R").unwrap();
+    static ref PDF_OBJECT_STREAM: Regex = Regex::new(r"(?s)<<([^>]*?/Type\s*/ObjStm[^>]*?)>>\s*stream\r?\n").unwrap();
+    static ref PDF_FIRST: Regex = Regex::new(r"/First\s+(\d+)").unwrap();
+    static ref XMP_PACKET: Regex = Regex::new(r"(?s)<x:xmpmeta.*?</x:xmpmeta>").unwrap();
+
+    static ref PDF_DATE: regex::Regex = regex::Regex::new(r"^(\d{4})(\d{2})?(\d{2})?(\d{2})?(\d{2})?(\d{2})?").unwrap();
+    static ref XMP_DATE: regex::Regex = regex::Regex::new(r"^(\d{4})(?:-(\d{2}))?(?:-(\d{2}))?(?:T(\d{2}):(\d{2})(?::(\d{2}))?)?").unwrap();
+    static ref RDF_ITEM: regex::Regex = regex::Regex::new(r"(?s)<rdf:li(?:\s[^>]*)?>(.*?)</rdf:li>").unwrap();
+    static ref XML_ENTITY: regex::Regex = regex::Regex::new(r"&(#x[0-9a-fA-F]+|#[0-9]+|amp|lt|gt|quot|apos);").unwrap();
+
+    //element and attribute patterns of every XMP and office property that is read
+    static ref XML_PROPERTIES: HashMap<&'static str, (regex::Regex, regex::Regex)> = XMP_KEYS
+        .iter()
+        .chain(DOC_KEYS.iter())
+        .flat_map(|(_, properties)| properties.iter())
+        .map(|name| {
+            let escaped = regex::escape(name);
+            let element = regex::Regex::new(&format!(r"(?s)<{0}(?:\s[^>]*)?>(.*?)</{0}>", escaped)).unwrap();
+            let attribute = regex::Regex::new(&format!(r#"\s{}\s*=\s*"([^"]*)""#, escaped)).unwrap();
+            (*name, (element, attribute))
+        })
+        .collect();
+}
+
+
+
+/** {pdf:Key} from the info dictionary or the XMP packet of PDF files */
+#[derive(Default)]
+pub struct PdfProvider {
+    cache: FileCache<Fields>,
+}
+
+
+impl Provider for PdfProvider {
+    fn provides(&self, name: &str) -> bool {
+        name == "pdf"
+    }
+
+    fn check(&self, _name: &str, arg: Option<&str>) -> Result<(), String> {
+        check_key(arg, &PDF_KEYS, &PDF_DATES)
+    }
+
+    fn value(&self, _name: &str, arg: Option<&str>, context: &Context) -> Option<String> {
+        let fields = self.cache.get(context.path, read_pdf)?;
+        field_value(&fields, arg?, &PDF_DATES)
+    }
+}
+
+
+
+/** {doc:key} from the core properties of OOXML and ODF files */
+#[derive(Default)]
+pub struct OfficeProvider {
+    cache: FileCache<Fields>,
+}
+
+
+impl Provider for OfficeProvider {
+    fn provides(&self, name: &str) -> bool {
+        name == "doc"
+    }
+
+    fn check(&self, _name: &str, arg: Option<&str>) -> Result<(), String> {
+        let keys: Vec<&str> = DOC_KEYS.iter().map(|(key, _)| *key).collect();
+        check_key(arg, &keys, &DOC_DATES)
+    }
+
+    fn value(&self, _name: &str, arg: Option<&str>, context: &Context) -> Option<String> {
+        let fields = self.cache.get(context.path, read_office)?;
+        field_value(&fields, arg?, &DOC_DATES)
+    }
+}
+
+
+
+fn field_value(fields: &Fields, arg: &str, dates: &[&str]) -> Option<String> {
+    let (key, format) = split_key(arg);
+    let value = fields.get(key)?;
+
+    if dates.contains(&key) {
+        return parse_date(value).map(|date| date.format(format.unwrap_or("%Y-%m-%d")).to_string());
+    }
+
+    clean_value(value)
+}
+
+
+//metadata is free text, keep it on one line and out of the directory structure,
+//a value naming the current or parent directory is missing
+fn clean_value(value: &str) -> Option<String> {
+    let value = value
+        .replace(['/', '\\'], "-")
+        .split(|c: char| c.is_whitespace() || c.is_control())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<&str>>()
+        .join(" ");
+
+    match value.as_str() {
+        "" | "." | ".." => None,
+        _ => Some(value),
+    }
+}
+
+
+/** PDF dates "D:20230115123000+01'00'" and XMP dates "2023-01-15T12:30:00+01:00" */
+fn parse_date(value: &str) -> Option<NaiveDateTime> {
+    let value = value.trim().trim_start_matches("D:");
+    let pattern: &regex::Regex = if value.get(4..5) == Some("-") { &XMP_DATE } else { &PDF_DATE };
+
+    let captures = pattern.captures(value)?;
+    let part = |index: usize, default: u32| {
+        captures.get(index).and_then(|m| m.as_str().parse::<u32>().ok()).unwrap_or(default)
+    };
+
+    NaiveDate::from_ymd_opt(part(1, 0) as i32, part(2, 1), part(3, 1))?.and_hms_opt(part(4, 0), part(5, 0), part(6, 0))
+}
+
+
+
+fn read_pdf(path: &Path) -> Option<Fields> {
+    let data = fs::read(path).ok()?;
+    pdf_fields(&data)
+}
+
+
+fn pdf_fields(data: &[u8]) -> Option<Fields> {
+    if !data.starts_with(b"%PDF") {
+        return None;
+    }
+
+    let mut fields = Fields::new();
+    let pdf = Pdf::new(data);
+
+    //the last /Info is the one of the latest incremental update
+    if let Some(captures) = PDF_INFO.captures_iter(data).last() {
+        if let Some(dictionary) = object_id(&captures).and_then(|id| pdf.object(id)) {
+            fields.extend(dictionary_strings(&dictionary, &pdf));
+        }
+    }
+
+    if let Some(xmp) = XMP_PACKET.find(data) {
+        let xmp = String::from_utf8_lossy(xmp.as_bytes());
+        for (key, properties) in XMP_KEYS.iter() {
+            if fields.get(*key).map(|value| value.trim().is_empty()).unwrap_or(true) {
+                if let Some(value) = properties.iter().find_map(|p| xml_value(&xmp, p)) {
+                    fields.insert(key.to_string(), value);
+                }
+            }
+        }
+    }
+
+    Some(fields)
+}
+
+
+/** a PDF file indexed once, its object streams are only decompressed if an object is missing from the body */
+struct Pdf<'a> {
+    data: &'a [u8],
+    //(number, generation) -> offset of the body of its last definition
+    objects: HashMap<(usize, usize), usize>,
+    streams: OnceCell<Vec<(usize, Vec<u8>)>>,
+}
+
+
+impl<'a> Pdf<'a> {
+    fn new(data: &'a [u8]) -> Pdf<'a> {
+        let objects = PDF_OBJECT
+            .captures_iter(data)
+            .filter_map(|captures| Some((object_id(&captures)?, captures.get(0)?.end())))
+            .collect();
+
+        Pdf { data, objects, streams: OnceCell::new() }
+    }
+
+
+    //body of "N G obj", looked up in compressed object streams when it is not in the file body
+    fn object(&self, (number, generation): (usize, usize)) -> Option<Cow<'a, [u8]>> {
+        if let Some(offset) = self.objects.get(&(number, generation)) {
+            return Some(Cow::Borrowed(&self.data[*offset..]));
+        }
+
+        self.streams
+            .get_or_init(|| object_streams(self.data))
+            .iter()
+            .find_map(|(first, stream)| stream_object(stream, *first, number))
+            .map(Cow::Owned)
+    }
+}
+
+
+//number and generation of "N G obj" and "N G R"
+fn object_id(captures: &Captures) -> Option<(usize, usize)> {
+    let number = String::from_utf8_lossy(&captures[1]).parse().ok()?;
+    let generation = String::from_utf8_lossy(&captures[2]).parse().ok()?;
+    Some((number, generation))
+}
+
+
+//decompressed /Type /ObjStm streams, with the offset of their first object
+fn object_streams(data: &[u8]) -> Vec<(usize, Vec<u8>)> {
+    PDF_OBJECT_STREAM
+        .captures_iter(data)
+        .filter_map(|captures| {
+            let header = PDF_FIRST.captures(captures.get(1)?.as_bytes())?;
+            let first = String::from_utf8_lossy(&header[1]).parse::<usize>().ok()?;
+
+            let mut decoded = Vec::new();
+            ZlibDecoder::new(&data[captures.get(0)?.end()..]).read_to_end(&mut decoded).ok()?;
+            Some((first, decoded))
+        })
+        .collect()
+}
+
+
+//objects are listed as "number offset" pairs before the first one
+fn stream_object(stream: &[u8], first: usize, number: usize) -> Option<Vec<u8>> {
+    let numbers: Vec<usize> = String::from_utf8_lossy(stream.get(..first)?)
+        .split_whitespace()
+        .filter_map(|n| n.parse::<usize>().ok())
+        .collect();
+
+    let pairs: Vec<(usize, usize)> = numbers.chunks(2).filter(|p| p.len() == 2).map(|p| (p[0], p[1])).collect();
+    let index = pairs.iter().position(|(n, _)| *n == number)?;
+    let end = pairs.get(index + 1).map(|(_, offset)| first + offset).unwrap_or(stream.len());
+
+    stream.get(first + pairs[index].1..end).map(|object| object.to_vec())
+}
+
+
+
+/** string entries of the dictionary at the start of an object, references are followed once */
+fn dictionary_strings(object: &[u8], pdf: &Pdf) -> Fields {
+    let mut fields = Fields::new();
+    let mut position = match find(object, b"<<") {
+        Some(start) => start + 2,
+        None => return fields,
+    };
+
+    let mut depth = 0;
+    while position < object.len() {
+        match object[position] {
+            b'>' if object.get(position + 1) == Some(&b'>') => {
+                if depth == 0 {
+                    break;
+                }
+                depth -= 1;
+                position += 2;
+            }
+            b'<' if object.get(position + 1) == Some(&b'<') => {
+                depth += 1;
+                position += 2;
+            }
+            b'/' if depth == 0 => {
+                let (key, next) = read_name(object, position + 1);
+                position = skip_whitespace(object, next);
+
+                let value = match object.get(position) {
+                    Some(b'(') => read_literal(object, position, &mut position),
+                    Some(b'<') if object.get(position + 1) != Some(&b'<') => read_hex(object, position, &mut position),
+                    Some(b'0'..=b'9') => read_reference(object, position, pdf),
+                    _ => None,
+                };
+
+                if let Some(value) = value {
+                    fields.insert(key, decode_text(&value));
+                }
+            }
+            b'(' => {
+                read_literal(object, position, &mut position);
+            }
+            _ => position += 1,
+        }
+    }
+
+    fields
+}
+
+
+fn find(data: &[u8], needle: &[u8]) -> Option<usize> {
+    data.windows(needle.len()).position(|window| window == needle)
+}
+
+
+fn skip_whitespace(data: &[u8], mut position: usize) -> usize {
+    while data.get(position).map(|b| b.is_ascii_whitespace()).unwrap_or(false) {
+        position += 1;
+    }
+    position
+}
+
+
+fn read_name(data: &[u8], start: usize) -> (String, usize) {
+    let end = data[start..]
+        .iter()
+        .position(|b| b.is_ascii_whitespace() || b"/<>[]()".contains(b))
+        .map(|offset| start + offset)
+        .unwrap_or(data.len());
+
+    (String::from_utf8_lossy(&data[start..end]).to_string(), end)
+}
+
+
+//"(text with \) escapes and (balanced) parens)"
+fn read_literal(data: &[u8], start: usize, end: &mut usize) -> Option<Vec<u8>> {
+    let mut value = Vec::new();
+    let mut depth = 0;
+    let mut position = start + 1;
+
+    while let Some(&b) = data.get(position) {
+        position += 1;
+        match b {
+            b'\\' => {
+                let escaped = *data.get(position)?;
+                position += 1;
+                match escaped {
+                    b'n' => value.push(b'\n'),
+                    b'r' => value.push(b'\r'),
+                    b't' => value.push(b'\t'),
+                    b'b' => value.push(8),
+                    b'f' => value.push(12),
+                    b'\r' | b'\n' => {
+                        if escaped == b'\r' && data.get(position) == Some(&b'\n') {
+                            position += 1;
+                        }
+                    }
+                    b'0'..=b'7' => {
+                        let mut code = u32::from(escaped - b'0');
+                        for _ in 0..2 {
+                            match data.get(position) {
+                                Some(digit @ b'0'..=b'7') => {
+                                    code = code * 8 + u32::from(digit - b'0');
+                                    position += 1;
+                                }
+                                _ => break,
+                            }
+                        }
+                        value.push(code as u8);
+                    }
+                    other => value.push(other),
+                }
+            }
+            b'(' => {
+                depth += 1;
+                value.push(b);
+            }
+            b')' if depth == 0 => {
+                *end = position;
+                return Some(value);
+            }
+            b')' => {
+                depth -= 1;
+                value.push(b);
+            }
+            _ => value.push(b),
+        }
+    }
+
+    *end = data.len();
+    None
+}
+
+
+fn read_hex(data: &[u8], start: usize, end: &mut usize) -> Option<Vec<u8>> {
+    let close = start + data[start..].iter().position(|b| *b == b'>')?;
+    *end = close + 1;
+
+    let mut digits: Vec<u8> = data[start + 1..close]
+        .iter()
+        .filter_map(|b| (*b as char).to_digit(16).map(|d| d as u8))
+        .collect();
+
+    if digits.len() % 2 == 1 {
+        digits.push(0);
+    }
+
+    Some(digits.chunks(2).map(|pair| pair[0] * 16 + pair[1]).collect())
+}
+
+
+//"/Title 12 0 R" stores the string in its own object
+fn read_reference(object: &[u8], start: usize, pdf: &Pdf) -> Option<Vec<u8>> {
+    let captures = PDF_REFERENCE.captures(&object[start..])?;
+    let target = pdf.object(object_id(&captures)?)?;
+
+    let position = skip_whitespace(&target, 0);
+    let mut end = 0;
+    match target.get(position) {
+        Some(b'(') => read_literal(&target, position, &mut end),
+        Some(b'<') => read_hex(&target, position, &mut end),
+        _ => None,
+    }
+}
+
+
+//UTF-16BE and UTF-8 with a byte order mark, PDFDocEncoding read as Latin-1 otherwise
+fn decode_text(bytes: &[u8]) -> String {
+    if bytes.starts_with(&[0xFE, 0xFF]) {
+        let units: Vec<u16> = bytes[2..]
+            .chunks(2)
+            .filter(|pair| pair.len() == 2)
+            .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+            .collect();
+        return String::from_utf16_lossy(&units);
+    }
+
+    if let Some(utf8) = bytes.strip_prefix(&[0xEF, 0xBB, 0xBF]) {
+        return String::from_utf8_lossy(utf8).to_string();
+    }
+
+    bytes.iter().map(|b| *b as char).collect()
+}
+
+
+
+fn read_office(path: &Path) -> Option<Fields> {
+    let mut archive = zip::ZipArchive::new(File::open(path).ok()?).ok()?;
+
+    let mut xml = String::new();
+    for name in &["docProps/core.xml", "meta.xml"] {
+        if let Ok(mut file) = archive.by_name(name) {
+            file.read_to_string(&mut xml).ok()?;
+            break;
+        }
+    }
+
+    if xml.is_empty() {
+        return None;
+    }
+
+    Some(office_fields(&xml))
+}
+
+
+fn office_fields(xml: &str) -> Fields {
+    DOC_KEYS
+        .iter()
+        .filter_map(|(key, elements)| {
+            let value = elements.iter().find_map(|element| xml_value(xml, element))?;
+            Some((key.to_string(), value))
+        })
+        .collect()
+}
+
+
+
+/** text of an element, the first rdf:li of XMP lists, or an attribute of the same name */
+fn xml_value(xml: &str, name: &str) -> Option<String> {
+    let (element, attribute) = XML_PROPERTIES.get(name)?;
+
+    let raw = match element.captures(xml) {
+        Some(captures) => {
+            let content = captures.get(1)?.as_str();
+            match RDF_ITEM.captures(content) {
+                Some(item) => item.get(1)?.as_str().to_string(),
+                None => content.to_string(),
+            }
+        }
+        None => attribute.captures(xml)?.get(1)?.as_str().to_string(),
+    };
+
+    let value = xml_unescape(raw.trim());
+    if value.is_empty() {
+        None
+    } else {
+        Some(value)
+    }
+}
+
+
+fn xml_unescape(text: &str) -> String {
+    XML_ENTITY
+        .replace_all(text, |captures: &regex::Captures| {
+            let name = &captures[1];
+            let code = if let Some(hex) = name.strip_prefix("#x") {
+                u32::from_str_radix(hex, 16).ok()
+            } else if let Some(decimal) = name.strip_prefix('#') {
+                decimal.parse::<u32>().ok()
+            } else {
+                None
+            };
+
+            match name {
+                "amp" => String::from("&"),
+                "lt" => String::from("<"),
+                "gt" => String::from(">"),
+                "quot" => String::from("\""),
+                "apos" => String::from("'"),
+                _ => code.and_then(std::char::from_u32).map(String::from).unwrap_or_default(),
+            }
+        })
+        .to_string()
+}
+
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use flate2::write::ZlibEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+
+    #[test]
+    fn pdf_info_dictionary() {
+        let pdf = b"%PDF-1.4\n1 0 obj\n<< /Type /Catalog >>\nendobj\n\
+            2 0 obj\n<< /Title (Lease \\(signed\\) 2019/20) /Author <FEFF004A00F6007200670065006E>\n\
+            /CreationDate (D:20190314093000+01'00') /Producer 3 0 R /Trapped /False >>\nendobj\n\
+            3 0 obj\n(Scanner\\040v2)\nendobj\n\
+            trailer\n<< /Root 1 0 R /Info 2 0 R >>\n%%EOF";
+
+        let fields = pdf_fields(pdf).unwrap();
+        assert_eq!(fields["Title"], "Lease (signed) 2019/20");
+        assert_eq!(fields["Author"], "Jörgen");
+        assert_eq!(fields["Producer"], "Scanner v2");
+        assert!(!fields.contains_key("Trapped"));
+
+        assert_eq!(field_value(&fields, "Title", &PDF_DATES).unwrap(), "Lease (signed) 2019-20");
+        assert_eq!(field_value(&fields, "CreationDate", &PDF_DATES).unwrap(), "2019-03-14");
+        assert_eq!(field_value(&fields, "CreationDate:%Y%m%d-%H%M", &PDF_DATES).unwrap(), "20190314-0930");
+    }
+
+
+    #[test]
+    fn pdf_object_stream_and_xmp() {
+        let objects = b"5 0 6 32 << /Title (Compressed title) >> << /Author (nobody) >>";
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(objects).unwrap();
+
+        let mut pdf = b"%PDF-1.5\n4 0 obj\n<< /Type /ObjStm /N 2 /First 9 /Filter /FlateDecode >>\nstream\n".to_vec();
+        pdf.extend(encoder.finish().unwrap());
+        pdf.extend_from_slice(b"\nendstream\nendobj\n<x:xmpmeta xmlns:x=\"adobe:ns:meta/\"><rdf:Description \
+            xmp:CreateDate=\"2021-06-01T08:00:00Z\"><dc:title><rdf:Alt><rdf:li xml:lang=\"x-default\">Ignored</rdf:li>\
+            </rdf:Alt></dc:title><dc:creator><rdf:Seq><rdf:li>Ann &amp; Bob</rdf:li></rdf:Seq></dc:creator>\
+            </rdf:Description></x:xmpmeta>\n");
+        pdf.extend_from_slice(b"9 0 obj\n<< /Type /XRef /Info 5 0 R /Size 10 >>\nendobj\n%%EOF");
+
+        let fields = pdf_fields(&pdf).unwrap();
+        assert_eq!(fields["Title"], "Compressed title");
+        assert_eq!(fields["Author"], "Ann & Bob");
+        assert_eq!(field_value(&fields, "CreationDate", &PDF_DATES).unwrap(), "2021-06-01");
+
+        //objects of the body are found without decompressing anything
+        let indexed = Pdf::new(&pdf);
+        assert!(indexed.object((9, 0)).unwrap().starts_with(b"\n<< /Type /XRef"));
+        assert!(indexed.streams.get().is_none());
+        assert!(indexed.object((6, 0)).unwrap().starts_with(b"<< /Author"));
+        assert_eq!(indexed.streams.get().unwrap().len(), 1);
+    }
+
+
+    #[test]
+    fn path_like_values() {
+        let fields: Fields = [("Title", ".."), ("Subject", " . "), ("Author", "\t\n"), ("Keywords", "../etc")]
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+
+        assert_eq!(field_value(&fields, "Title", &PDF_DATES), None);
+        assert_eq!(field_value(&fields, "Subject", &PDF_DATES), None);
+        assert_eq!(field_value(&fields, "Author", &PDF_DATES), None);
+        assert_eq!(field_value(&fields, "Keywords", &PDF_DATES).unwrap(), "..-etc");
+    }
+
+
+    #[test]
+    fn office_core_properties() {
+        let core = r#"<cp:coreProperties xmlns:cp="..." xmlns:dc="..."><dc:title>Budget &lt;2024&gt;</dc:title>
+            <dc:creator>Alice</dc:creator><dcterms:created xsi:type="dcterms:W3CDTF">2024-02-29T10:00:00Z</dcterms:created>
+            </cp:coreProperties>"#;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("budget.docx");
+        let mut writer = zip::ZipWriter::new(File::create(&path).unwrap());
+        writer.start_file("docProps/core.xml", zip::write::SimpleFileOptions::default()).unwrap();
+        writer.write_all(core.as_bytes()).unwrap();
+        writer.finish().unwrap();
+
+        let fields = read_office(&path).unwrap();
+        assert_eq!(fields["title"], "Budget <2024>");
+        assert_eq!(fields["author"], "Alice");
+        assert_eq!(field_value(&fields, "created:%d.%m.%Y", &DOC_DATES).unwrap(), "29.02.2024");
+
+        let odf = office_fields("<office:meta><meta:initial-creator>Carl</meta:initial-creator><dc:creator>Dana</dc:creator></office:meta>");
+        assert_eq!(odf["author"], "Carl");
+        assert_eq!(odf["lastmodifiedby"], "Dana");
+    }
+
+
+    #[test]
+    fn check_keys() {
+        assert!(check_key(Some("Title"), &PDF_KEYS, &PDF_DATES).is_ok());
+        assert!(check_key(Some("ModDate:%Y"), &PDF_KEYS, &PDF_DATES).is_ok());
+        assert!(check_key(Some("title"), &PDF_KEYS, &PDF_DATES).is_err());
+        assert!(check_key(Some("Title:%Y"), &PDF_KEYS, &PDF_DATES).is_err());
+        assert!(check_key(None, &PDF_KEYS, &PDF_DATES).is_err());
+    }
+}
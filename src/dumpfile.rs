@@ -16,30 +16,49 @@ pub struct Operation {
 
 
 
+/** a symlink pointed at the new path of its target once the operations ran, from source to target like them */
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Relink {
+    pub source: PathBuf, 
+    pub target: PathBuf, 
+    pub old_contents: PathBuf, 
+    pub new_contents: PathBuf, 
+}
+
+
+
 #[derive(Serialize, Deserialize)]
 struct DumpFormat {
     date: String, 
-    operations: Operations
+    operations: Operations, 
+    //dumps written before relinks were recorded have none
+    #[serde(default)]
+    relinks: Relinks, 
 }
 
 
 pub type Operations = Vec<Operation>; 
+pub type Relinks = Vec<Relink>; 
 
 
 //dump to operations
-pub fn dump_to_file(operations: &[Operation]) -> Result<()> {
+pub fn dump_to_file(operations: &[Operation], relinks: &[Relink]) -> Result<()> {
+    //a json dump file with info on your last update
     let now = chrono::Local::now(); 
-    
-    
+    let filename = "rx-".to_string() + &now.format("%Y-%m-%d_%H%M%S").to_string() + ".json";
+
+    write_dump(Path::new(&filename), operations, relinks)
+}
+
+
+pub fn write_dump(filepath: &Path, operations: &[Operation], relinks: &[Relink]) -> Result<()> {
+    let filename = filepath.to_string_lossy().to_string(); 
     let dump = DumpFormat{
-        date: now.format("%Y-%m-%d %H:%M:%S").to_string() , 
+        date: chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string() , 
         operations: operations.to_vec(),
+        relinks: relinks.to_vec(), 
     }; 
 
-    
-    //a json dump file with info on your last update
-    let filename = "rx-".to_string() + &now.format("%Y-%m-%d_%H%M%S").to_string() + ".json";
-
 
     let file = match File::create(&filename) {
         Ok(file) => file, 
@@ -64,7 +83,7 @@ pub fn dump_to_file(operations: &[Operation]) -> Result<()> {
 
 
 
-pub fn read_from_file(filepath: &Path) -> Result<Operations> {
+pub fn read_from_file(filepath: &Path) -> Result<(Operations, Relinks)> {
     let file = match File::open(&filepath) {
         Ok(file) => file,
         Err(_) => {
@@ -87,7 +106,7 @@ pub fn read_from_file(filepath: &Path) -> Result<Operations> {
     };
 
 
-    Ok(dump.operations)
+    Ok((dump.operations, dump.relinks))
 }
 
 
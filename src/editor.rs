@@ -0,0 +1,244 @@
+//build a rename map by editing the selected paths in $EDITOR
+
+use crate::error::*;
+use crate::renamer::RenameMap;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::Command;
+
+
+
+const HEADER: &str = "\
+# Edit the paths below to rename them, then save and quit.
+# Lines starting with '#' are ignored. Delete a line to skip that path.
+# Do not change or add the numbers at the beginning of the lines.
+";
+
+
+
+/** open the paths in the user editor and read the renames back */
+pub fn edit_rename_map(paths: &[PathBuf]) -> Result<RenameMap> {
+    let mut contents = format!("{}{}", HEADER, format_paths(paths));
+
+    loop {
+        let edited = run_editor(&contents)?;
+
+        match parse_edited(&edited, paths) {
+            Ok(rename_map) => return Ok(rename_map),
+            Err(errors) => contents = annotate_errors(&edited, &errors),
+        }
+    }
+}
+
+
+
+//one numbered line per path, the number maps the line back to its source
+fn format_paths(paths: &[PathBuf]) -> String {
+    let width = paths.len().to_string().len();
+
+    paths.iter()
+        .enumerate()
+        .map(|(index, path)| format!("{:0width$}\t{}\n", index + 1, path.display(), width = width))
+        .collect()
+}
+
+
+
+//write contents into a temp file, open the editor and read the file back
+fn run_editor(contents: &str) -> Result<String> {
+    let mut file = match tempfile::Builder::new().prefix("rx-").suffix(".txt").tempfile() {
+        Ok(file) => file,
+        Err(err) => {
+            return Err(Error {
+                kind: ErrorKind::CreateFile,
+                value: Some(err.to_string()),
+            })
+        }
+    };
+
+    if let Err(err) = file.write_all(contents.as_bytes()).and_then(|_| file.flush()) {
+        return Err(Error {
+            kind: ErrorKind::CreateFile,
+            value: Some(format!("{}\n{}", file.path().display(), err)),
+        });
+    }
+
+    let editor = get_editor();
+    let mut words = editor.split_whitespace();
+    let program = words.next().unwrap_or("vi");
+
+    match Command::new(program).args(words).arg(file.path()).status() {
+        Ok(status) if status.success() => {}
+        Ok(status) => {
+            return Err(Error {
+                kind: ErrorKind::Editor,
+                value: Some(format!("'{}' exited with {}", editor, status)),
+            })
+        }
+        Err(err) => {
+            return Err(Error {
+                kind: ErrorKind::Editor,
+                value: Some(format!("'{}'\n{}", editor, err)),
+            })
+        }
+    }
+
+    match fs::read_to_string(file.path()) {
+        Ok(edited) => Ok(edited),
+        Err(_) => Err(Error {
+            kind: ErrorKind::ReadFile,
+            value: Some(file.path().to_string_lossy().to_string()),
+        }),
+    }
+}
+
+
+
+//same lookup order as most unix tools
+fn get_editor() -> String {
+    env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .ok()
+        .filter(|editor| !editor.trim().is_empty())
+        .unwrap_or_else(|| {
+            if cfg!(windows) {
+                String::from("notepad")
+            } else {
+                String::from("vi")
+            }
+        })
+}
+
+
+
+/** parse the edited file into a rename map, collecting every error found */
+fn parse_edited(edited: &str, paths: &[PathBuf]) -> std::result::Result<RenameMap, Vec<String>> {
+    let mut errors: Vec<String> = Vec::new();
+    let mut seen_lines: HashMap<usize, usize> = HashMap::new();
+    let mut seen_targets: HashMap<PathBuf, usize> = HashMap::new();
+    let mut rename_map = RenameMap::new();
+    let mut line_count = 0;
+
+    for (line_number, line) in edited.lines().enumerate().map(|(i, l)| (i + 1, l)) {
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        line_count += 1;
+
+        let (index, name) = match line.find('\t') {
+            Some(tab) => (line[..tab].trim().parse::<usize>().ok(), &line[tab + 1..]),
+            None => (None, ""),
+        };
+
+        let index = match index {
+            Some(index) if index >= 1 && index <= paths.len() => index,
+            _ => {
+                errors.push(format!("line {}: unknown line number, do not add or renumber lines", line_number));
+                continue;
+            }
+        };
+
+        if let Some(previous) = seen_lines.insert(index, line_number) {
+            errors.push(format!("line {}: number {} already used on line {}", line_number, index, previous));
+            continue;
+        }
+
+        let target = PathBuf::from(name);
+        if name.trim().is_empty() || target.file_name().is_none() {
+            errors.push(format!("line {}: empty name", line_number));
+            continue;
+        }
+
+        if let Some(previous) = seen_targets.insert(target.clone(), line_number) {
+            errors.push(format!("line {}: '{}' is also the target of line {}", line_number, name, previous));
+            continue;
+        }
+
+        let source = &paths[index - 1];
+        if target != *source {
+            rename_map.insert(target, source.clone());
+        }
+    }
+
+    if line_count > paths.len() {
+        errors.insert(0, format!("line count changed: expected at most {} lines, found {}", paths.len(), line_count));
+    }
+
+    if errors.is_empty() {
+        Ok(rename_map)
+    } else {
+        Err(errors)
+    }
+}
+
+
+
+//put the errors on top of the edited contents so the user can fix them
+fn annotate_errors(edited: &str, errors: &[String]) -> String {
+    let mut contents = String::from(HEADER);
+
+    for error in errors {
+        contents.push_str(&format!("# error: {}\n", error));
+    }
+
+    for line in edited.lines().filter(|l| !l.starts_with('#')) {
+        contents.push_str(line);
+        contents.push('\n');
+    }
+
+    contents
+}
+
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn mock_paths() -> Vec<PathBuf> {
+        vec![
+            PathBuf::from("dir/file_1.txt"),
+            PathBuf::from("dir/file_2.txt"),
+            PathBuf::from("dir/file_3.txt"),
+        ]
+    }
+
+
+    #[test]
+    fn edited_lines_to_rename_map() {
+        let paths = mock_paths();
+        let edited = "# comment\n1\tdir/first.txt\n2\tdir/file_2.txt\n";
+
+        let rename_map = parse_edited(edited, &paths).unwrap();
+        assert_eq!(rename_map.len(), 1);
+        assert_eq!(rename_map[&PathBuf::from("dir/first.txt")], paths[0]);
+        assert!(!rename_map.values().any(|p| p == &paths[2]));
+    }
+
+
+    #[test]
+    fn formatted_paths_parse_back_unchanged() {
+        let paths = mock_paths();
+        let rename_map = parse_edited(&format_paths(&paths), &paths).unwrap();
+        assert!(rename_map.is_empty());
+    }
+
+
+    #[test]
+    fn invalid_edits_are_reported() {
+        let paths = mock_paths();
+        let edited = "1\tdir/same.txt\n2\tdir/same.txt\n3\t\n4\tdir/extra.txt\n";
+
+        let errors = parse_edited(edited, &paths).unwrap_err();
+        assert_eq!(errors.len(), 4);
+        assert!(errors[0].starts_with("line count changed"));
+
+        let annotated = annotate_errors(edited, &errors);
+        assert!(annotated.contains("# error: line 2:"));
+        assert!(annotated.ends_with("4\tdir/extra.txt\n"));
+    }
+}
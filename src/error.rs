@@ -8,10 +8,16 @@ pub enum  ErrorKind {
     CreateBackup, 
     CreateFile, 
     CreateSymlink, 
+    CsvParse, 
+    Editor, 
     ExistingPath, 
+    InvalidName, 
+    InvalidMapping, 
     JsonParse, 
+    OtherFilesystem, 
     ReadFile, 
     Rename, 
+    RenameRoot, 
     SameFilename, 
     SolveOrder
 }
@@ -31,10 +37,16 @@ impl Error  {
             CreateBackup => "Cannot create a backup of", 
             CreateFile => "Cannot create file", 
             CreateSymlink => "Cannot create symlink", 
+            CsvParse => "Cannot parse CSV file", 
+            Editor => "Cannot run editor", 
             ExistingPath => "Conflict with existing path", 
+            InvalidName => "Cannot build a new name for", 
+            InvalidMapping => "Invalid rename mapping", 
             JsonParse => "Cannot parse JSON  file",
+            OtherFilesystem => "Skipped a subtree on another filesystem:", 
             ReadFile => "Cannot open/read file",
             Rename => "Cannot Rename", 
+            RenameRoot => "Cannot rename a root along with the paths under it", 
             SameFilename => "Files will have the same name", 
             SolveOrder => "Cannot solve sorting problem"
         }
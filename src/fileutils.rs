@@ -2,65 +2,157 @@
 
 //get the path of the run command made by the user
 
-use crate::config::RunMode;
+use crate::config::{RunMode, SortOrder};
 
 use crate::error::*; 
-use std::collections::HashMap;
+use crate::output::Printer;
+use crate::walker::{self, WalkOptions};
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::env;
+use std::ffi::OsStr;
 use std::fs;
-use std::path::Path;
+use std::io::{self, Read};
+use std::path::{Component, Path};
 use std::path::PathBuf;
-use path_abs::PathAbs;
-use walkdir::{ DirEntry, WalkDir}; 
+use std::time::UNIX_EPOCH;
 
 
 pub type PathList = Vec<PathBuf>; 
 
 
 
-pub fn get_paths(mode: &RunMode) -> PathList {
+pub fn get_paths(mode: &RunMode, printer: &Printer) -> Result<PathList> {
     match mode {
         RunMode::Recursive { 
-            paths, 
-            max_depth, 
-            hidden } => {
-            
-                //detect of the file is hidden or not
-            // let mut path_list = PathList::new(); //list of paths available
-
-            let is_hidden = |f:&DirEntry| -> bool {
-                if !hidden && f.depth() > 0 {
-                  f.file_name().to_str().map(|s| !s.starts_with(".")).unwrap_or(false)
-                }  else {
-                  true // or hidden
+            roots, 
+            hidden, 
+            follow, 
+            one_file_system, 
+            filter, 
+            predicates, 
+            ignore, 
+            threads } => {
+            let options = WalkOptions {
+                hidden: *hidden, 
+                follow: *follow, 
+                one_file_system: *one_file_system, 
+                filter, 
+                predicates, 
+                ignore: *ignore, 
+                threads: *threads
+            }; 
+
+            Ok(walker::walk(roots, &options, printer))
+        },
+
+        RunMode::Simple(path_list) => Ok(path_list.iter().map(PathBuf::from).collect()), 
+
+        RunMode::Stdin { null, predicates } => {
+            let mut input = Vec::new(); 
+            if let Err(err) = io::stdin().read_to_end(&mut input) {
+                return Err(Error {
+                    kind: ErrorKind::ReadFile, 
+                    value: Some(format!("stdin: {}", err)), 
+                }); 
+            }
+
+            let mut path_list = split_paths(&input, *null); 
+            path_list.retain(|p| p.symlink_metadata().map(|m| predicates.matches(p, &m)).unwrap_or(false)); 
+            cleanup_paths(&mut path_list, true); 
+            Ok(path_list)
+        }
+
+        _ => Ok(PathList::new())
+    }
+}
+
+
+
+//split stdin input into paths, like `find` or `find -print0` output
+fn split_paths(input: &[u8], null: bool) -> PathList {
+    let separator = if null { b'\0' } else { b'\n' }; 
+
+    input.split(|b| *b == separator)
+        .map(|p| if !null && p.ends_with(b"\r") { &p[..p.len() - 1] } else { p })
+        .filter(|p| !p.is_empty())
+        .map(path_from_bytes)
+        .collect()
+}
+
+
+//names are kept byte for byte, they do not have to be UTF-8
+#[cfg(unix)]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    use std::os::unix::ffi::OsStrExt; 
+    PathBuf::from(OsStr::from_bytes(bytes))
+}
+
+
+#[cfg(not(unix))]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(bytes).to_string())
+}
+
+
+
+/** absolute path without resolving symlinks, "." and ".." are folded */
+pub fn absolute_path(path: &Path) -> io::Result<PathBuf> {
+    let joined = if path.is_absolute() { path.to_path_buf() } else { env::current_dir()?.join(path) }; 
+    Ok(normalize_path(&joined))
+}
+
+
+/** "." and ".." folded without touching the filesystem, a relative path keeps its leading ones */
+pub fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new(); 
+    for component in path.components() {
+        match component {
+            Component::CurDir => normalized.push(component), //only found at the start
+            Component::ParentDir => match normalized.components().next_back() {
+                Some(Component::Normal(_)) => {
+                    normalized.pop(); 
                 }
-            };
+                Some(Component::RootDir) | Some(Component::Prefix(_)) => {}
+                _ => normalized.push(component), 
+            }, 
+            component => normalized.push(component), 
+        }
+    }
 
-            let mut path_list = PathList::new(); 
+    normalized
+}
 
-            for path in paths {
-                let walkdir = match max_depth {
-                    Some(max_depth) => WalkDir::new(path).max_depth(*max_depth), 
-                    None => WalkDir::new(path)
-                }; 
 
 
-                let mut walk_list: PathList = walkdir
-                        .into_iter()
-                        .filter_entry(is_hidden)
-                        .filter_map(|e| e.ok())
-                        .map(|p| p.path().to_path_buf())
-                        .collect(); 
-                        //before accessing collect it needs to be converted to a buf then collected as a vector
+/** path relative to a directory, both absolute without "." or ".." */
+pub fn relative_path(path: &Path, base: &Path) -> PathBuf {
+    let common = path.components().zip(base.components()).take_while(|(a, b)| a == b).count(); 
 
-                        path_list.append(&mut walk_list); 
-            }
+    let mut relative: PathBuf = base.components().skip(common).map(|_| Component::ParentDir).collect(); 
+    relative.extend(path.components().skip(common)); 
+    relative
+}
 
-            path_list
 
-        },
 
-        RunMode::Simple(path_list) => path_list.iter().map(PathBuf::from).collect(), 
-        _ => PathList::new()
+
+/** root the path was found from, its parent directory when given directly */
+pub fn get_root(mode: &RunMode, path: &Path) -> PathBuf {
+    if let RunMode::Recursive { roots, .. } = mode {
+        let root = roots.iter()
+            .map(|root| Path::new(&root.path))
+            .filter(|root| path.starts_with(root))
+            .max_by_key(|root| root.components().count()); 
+
+        if let Some(root) = root {
+            return root.to_path_buf(); 
+        }
+    }
+
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(), 
+        _ => PathBuf::from("."), 
     }
 }
 
@@ -147,6 +239,93 @@ pub fn create_symlink(source: &Path, symlink_file: &Path) -> Result<()> {
 }
 
 
+/** order the selected paths, counters follow it while the solver picks the order of the renames */
+pub fn sort_paths(paths: &mut PathList, order: SortOrder, reverse: bool) {
+    let modified = |path: &PathBuf| path.symlink_metadata().and_then(|m| m.modified()).unwrap_or(UNIX_EPOCH); 
+    let size = |path: &PathBuf| path.symlink_metadata().map(|m| m.len()).unwrap_or(0); 
+
+    match order {
+        SortOrder::None => {}
+        SortOrder::Name => paths.sort(), 
+        SortOrder::Natural => paths.sort_by(|a, b| natural_path_cmp(a, b)), 
+        //stable sorts, equal keys keep the natural order
+        SortOrder::Mtime => {
+            paths.sort_by(|a, b| natural_path_cmp(a, b)); 
+            paths.sort_by_cached_key(modified); 
+        }
+        SortOrder::Size => {
+            paths.sort_by(|a, b| natural_path_cmp(a, b)); 
+            paths.sort_by_cached_key(size); 
+        }
+    }
+
+    if reverse {
+        paths.reverse(); 
+    }
+}
+
+
+
+//compare directory by directory so that a folder stays before its content
+fn natural_path_cmp(a: &Path, b: &Path) -> Ordering {
+    let mut a = a.components(); 
+    let mut b = b.components(); 
+
+    loop {
+        match (a.next(), b.next()) {
+            (Some(x), Some(y)) => match natural_cmp(&x.as_os_str().to_string_lossy(), &y.as_os_str().to_string_lossy()) {
+                Ordering::Equal => continue, 
+                ordering => return ordering, 
+            }, 
+            (x, y) => return x.is_some().cmp(&y.is_some()), 
+        }
+    }
+}
+
+
+/** "file2" before "file10", digit runs are compared by value */
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let mut a = a.chars().peekable(); 
+    let mut b = b.chars().peekable(); 
+
+    loop {
+        match (a.peek().copied(), b.peek().copied()) {
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let x = digit_run(&mut a); 
+                let y = digit_run(&mut b); 
+                let (x_value, y_value) = (x.trim_start_matches('0'), y.trim_start_matches('0')); 
+
+                //longer numbers are bigger once the leading zeros are gone, "007" after "7"
+                let ordering = x_value.len().cmp(&y_value.len())
+                    .then_with(|| x_value.cmp(y_value))
+                    .then_with(|| x.len().cmp(&y.len())); 
+                if ordering != Ordering::Equal {
+                    return ordering; 
+                }
+            }
+            (Some(x), Some(y)) => {
+                if x != y {
+                    return x.cmp(&y); 
+                }
+                a.next(); 
+                b.next(); 
+            }
+            (x, y) => return x.is_some().cmp(&y.is_some()), 
+        }
+    }
+}
+
+
+fn digit_run(chars: &mut std::iter::Peekable<std::str::Chars>) -> String {
+    let mut run = String::new(); 
+    while let Some(c) = chars.next_if(|c| c.is_ascii_digit()) {
+        run.push(c); 
+    }
+    run
+}
+
+
+
 /* cleanup the paths created  */
 pub fn cleanup_paths(paths: &mut PathList, keep_dirs: bool) {
     paths.retain(|path| {
@@ -162,10 +341,9 @@ pub fn cleanup_paths(paths: &mut PathList, keep_dirs: bool) {
     }); 
 
 
-    let abs_path_map: HashMap<PathAbs, PathBuf> = paths.drain(..)
-        .map(|f| (PathAbs::new(&f).unwrap(), f)).collect(); 
-
-    paths.append(&mut abs_path_map.values().cloned().collect()); 
+    //the first of the paths leading to the same place is kept, in the input order
+    let mut seen = HashSet::new(); 
+    paths.retain(|path| seen.insert(absolute_path(path).unwrap_or_else(|_| path.clone()))); 
 }
 
 
@@ -174,6 +352,8 @@ pub fn cleanup_paths(paths: &mut PathList, keep_dirs: bool) {
 #[cfg(test)]
 mod test {
     use super::*; 
+    use crate::config::WalkRoot;
+    use crate::filter::{PathFilter, Predicates};
     use std::{fs, io::Write}; 
 
 
@@ -186,7 +366,7 @@ mod test {
         ]; 
 
         let mode = RunMode::Simple(mock_files); 
-        let files = get_paths(&mode);
+        let files = get_paths(&mode, &Printer::silent()).unwrap();
         assert!(files.contains(&PathBuf::from("test_file.1.txt"))); 
         assert!(files.contains(&PathBuf::from("test_file_2.txt"))); 
         assert!(files.contains(&PathBuf::from("test_file_3.txt")));  
@@ -238,6 +418,117 @@ mod test {
 
 
 
+    #[test]
+    fn split_stdin_paths() {
+        let paths = split_paths(b"file_1.txt\ndir/file 2.txt\r\n\n", false); 
+        assert_eq!(paths, vec![PathBuf::from("file_1.txt"), PathBuf::from("dir/file 2.txt")]); 
+
+        let paths = split_paths(b"file\n1.txt\0file_2.txt\0", true); 
+        assert_eq!(paths, vec![PathBuf::from("file\n1.txt"), PathBuf::from("file_2.txt")]); 
+    }
+
+
+
+    #[cfg(unix)]
+    #[test]
+    fn split_non_utf8_paths() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let paths = split_paths(b"caf\xe9.txt\nb.txt", false); 
+        assert_eq!(paths[0].as_os_str().as_bytes(), b"caf\xe9.txt"); 
+        assert_eq!(paths[1], PathBuf::from("b.txt")); 
+    }
+
+
+
+    #[test]
+    fn normalize_paths() {
+        assert_eq!(normalize_path(Path::new("./links/../data/a.txt")), PathBuf::from("./data/a.txt"));
+        assert_eq!(normalize_path(Path::new("../x/../../y")), PathBuf::from("../../y"));
+        assert_eq!(normalize_path(Path::new("/a/../../b/./c")), PathBuf::from("/b/c"));
+        assert_eq!(relative_path(Path::new("/r/new/new.txt"), Path::new("/r/links")), PathBuf::from("../new/new.txt"));
+    }
+
+
+
+    #[test]
+    fn natural_order() {
+        assert_eq!(natural_cmp("file2.txt", "file10.txt"), Ordering::Less);
+        assert_eq!(natural_cmp("file010", "file9"), Ordering::Greater);
+        assert_eq!(natural_cmp("img7", "img007"), Ordering::Less);
+        assert_eq!(natural_cmp("a", "a1"), Ordering::Less);
+
+        let mut paths: PathList = ["b/file10", "b", "a b", "b/file2", "a/x"].iter().map(PathBuf::from).collect();
+        sort_paths(&mut paths, SortOrder::Natural, false);
+        assert_eq!(paths, ["a/x", "a b", "b", "b/file2", "b/file10"].iter().map(PathBuf::from).collect::<PathList>());
+
+        sort_paths(&mut paths, SortOrder::Name, true);
+        assert_eq!(paths, ["b/file2", "b/file10", "b", "a b", "a/x"].iter().map(PathBuf::from).collect::<PathList>());
+    }
+
+
+
+    #[test]
+    fn walk_with_filters() {
+        let tempdir = tempfile::tempdir().expect("Error creating temp directory");
+        let root = tempdir.path();
+
+        for dir in &["node_modules/pkg", "src"] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        for file in &["a.jpg", "node_modules/pkg/b.jpg", "src/c.jpg", "src/d.txt"] {
+            fs::File::create(root.join(file)).unwrap();
+        }
+
+        let mode = RunMode::Recursive {
+            roots: vec![WalkRoot { path: root.to_string_lossy().to_string(), min_depth: 0, max_depth: None }],
+            hidden: false,
+            follow: false,
+            one_file_system: false,
+            threads: 1,
+            filter: PathFilter::new(&["*.jpg"], &["node_modules/**"], None).unwrap(),
+            predicates: Predicates::default(),
+            ignore: false,
+        };
+
+        let mut files = get_paths(&mode, &Printer::silent()).unwrap();
+        files.sort();
+        assert_eq!(files, vec![root.join("a.jpg"), root.join("src/c.jpg")]);
+    }
+
+
+
+    #[test]
+    fn depth_per_root() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let root = tempdir.path();
+        fs::create_dir_all(root.join("a/b/c")).unwrap();
+        fs::create_dir_all(root.join("x/y")).unwrap();
+
+        let walk_root = |path: &str, min_depth, max_depth| WalkRoot { path: root.join(path).to_string_lossy().to_string(), min_depth, max_depth };
+        let mode = RunMode::Recursive {
+            roots: vec![walk_root("a", 1, Some(2)), walk_root("x", 0, None)],
+            hidden: false,
+            follow: false,
+            one_file_system: false,
+            threads: 1,
+            filter: PathFilter::default(),
+            predicates: Predicates::default(),
+            ignore: false,
+        };
+
+        let mut dirs = get_paths(&mode, &Printer::silent()).unwrap();
+        dirs.sort();
+        assert_eq!(dirs, vec![root.join("a/b"), root.join("a/b/c"), root.join("x"), root.join("x/y")]);
+
+        assert_eq!(crate::app::parse_depth_root("src:1..3"), Some(("src", Some(1), Some(3))));
+        assert_eq!(crate::app::parse_depth_root("c:\\photos:..2"), Some(("c:\\photos", None, Some(2))));
+        assert_eq!(crate::app::parse_depth_root("src:3..1"), None);
+        assert_eq!(crate::app::parse_depth_root("notes.txt"), None);
+    }
+
+
+
     //perform cleanu[]
     #[test]
     fn cleanup() {
@@ -337,6 +628,9 @@ mod test {
         for file in &non_listed_files {
             assert!(!mock_paths.contains(file));
         }
+
+        // Input order is kept
+        assert_eq!(mock_paths, listed_files);
     
     }
 
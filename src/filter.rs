@@ -0,0 +1,336 @@
+//include/exclude filters on the path relative to the walked root,
+//type, size and time predicates on the entry metadata
+
+use chrono::{Local, NaiveDate, NaiveDateTime, TimeZone};
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use regex::Regex;
+use std::fs::{self, Metadata};
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+
+
+#[derive(Debug, Default)]
+pub struct PathFilter {
+    include: Option<GlobSet>,
+    exclude: Option<GlobSet>,
+    matching: Option<Regex>,
+}
+
+
+impl PathFilter {
+    /** globs without a '/' match the name at any depth, the others match from the root */
+    pub fn new(include: &[&str], exclude: &[&str], matching: Option<&str>) -> Result<PathFilter, String> {
+        //"node_modules/**" also excludes the directory itself, so it is never walked into
+        let exclude: Vec<String> = exclude
+            .iter()
+            .flat_map(|pattern| match pattern.strip_suffix("/**") {
+                Some(directory) if !directory.is_empty() => {
+                    //still anchored to the root like the pattern it comes from
+                    let directory = if directory.contains('/') { directory.to_string() } else { format!("/{}", directory) };
+                    vec![pattern.to_string(), directory]
+                }
+                _ => vec![pattern.to_string()],
+            })
+            .collect();
+
+        let matching = match matching {
+            Some(expression) => match Regex::new(expression) {
+                Ok(regex) => Some(regex),
+                Err(err) => return Err(format!("Bad --where expression provided\n\n {}", err)),
+            },
+            None => None,
+        };
+
+        Ok(PathFilter {
+            include: build_globs(include.iter().cloned())?,
+            exclude: build_globs(exclude.iter().map(String::as_str))?,
+            matching,
+        })
+    }
+
+
+    /** excluded entries are skipped, and directories are not walked into */
+    pub fn is_excluded(&self, relative: &Path) -> bool {
+        match &self.exclude {
+            Some(exclude) => exclude.is_match(relative),
+            None => false,
+        }
+    }
+
+
+    /** whether a walked entry is listed */
+    pub fn is_selected(&self, relative: &Path) -> bool {
+        if let Some(include) = &self.include {
+            if !include.is_match(relative) {
+                return false;
+            }
+        }
+
+        match &self.matching {
+            Some(matching) => matching.is_match(&relative.to_string_lossy()),
+            None => true,
+        }
+    }
+}
+
+
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EntryType {
+    File,
+    Directory,
+    Symlink,
+}
+
+
+impl EntryType {
+    /** 'f', 'd' or 'l' as given to --type */
+    pub fn from_letter(letter: &str) -> Option<EntryType> {
+        match letter {
+            "f" => Some(EntryType::File),
+            "d" => Some(EntryType::Directory),
+            "l" => Some(EntryType::Symlink),
+            _ => None,
+        }
+    }
+
+
+    fn of(metadata: &Metadata) -> EntryType {
+        let file_type = metadata.file_type();
+        if file_type.is_symlink() {
+            EntryType::Symlink
+        } else if file_type.is_dir() {
+            EntryType::Directory
+        } else {
+            EntryType::File
+        }
+    }
+}
+
+
+
+/** conditions on the metadata of a listed entry, all of them must hold */
+#[derive(Debug, Default)]
+pub struct Predicates {
+    //any type when empty
+    pub types: Vec<EntryType>,
+    pub min_size: Option<u64>,
+    pub max_size: Option<u64>,
+    pub newer: Option<SystemTime>,
+    pub older: Option<SystemTime>,
+    pub empty: bool,
+}
+
+
+impl Predicates {
+    /** metadata is the one of the walk, links followed with --follow match their target and not --type l */
+    pub fn matches(&self, path: &Path, metadata: &Metadata) -> bool {
+        let entry_type = EntryType::of(metadata);
+        if !self.types.is_empty() && !self.types.contains(&entry_type) {
+            return false;
+        }
+
+        //sizes only make sense for regular files
+        if self.min_size.is_some() || self.max_size.is_some() {
+            if entry_type != EntryType::File {
+                return false;
+            }
+            if self.min_size.map(|min| metadata.len() < min).unwrap_or(false) || self.max_size.map(|max| metadata.len() > max).unwrap_or(false) {
+                return false;
+            }
+        }
+
+        if self.newer.is_some() || self.older.is_some() {
+            let modified = match metadata.modified() {
+                Ok(modified) => modified,
+                Err(_) => return false,
+            };
+            if self.newer.map(|newer| modified <= newer).unwrap_or(false) || self.older.map(|older| modified >= older).unwrap_or(false) {
+                return false;
+            }
+        }
+
+        if self.empty {
+            return match entry_type {
+                EntryType::File => metadata.len() == 0,
+                EntryType::Directory => fs::read_dir(path).map(|mut entries| entries.next().is_none()).unwrap_or(false),
+                EntryType::Symlink => false,
+            };
+        }
+
+        true
+    }
+}
+
+
+
+/** "+10M" is at least, "-1k" at most and "4Ki" exactly that size, as (min, max) */
+pub fn parse_size(arg: &str) -> Option<(Option<u64>, Option<u64>)> {
+    let (sign, size) = match arg.chars().next()? {
+        sign @ ('+' | '-') => (Some(sign), &arg[1..]),
+        _ => (None, arg),
+    };
+
+    let digits = size.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(size.len());
+    let number: f64 = size[..digits].parse().ok()?;
+    let unit: u64 = match size[digits..].to_lowercase().trim_end_matches('b') {
+        "" => 1,
+        "k" => 1000,
+        "m" => 1000u64.pow(2),
+        "g" => 1000u64.pow(3),
+        "t" => 1000u64.pow(4),
+        "ki" => 1024,
+        "mi" => 1024u64.pow(2),
+        "gi" => 1024u64.pow(3),
+        "ti" => 1024u64.pow(4),
+        _ => return None,
+    };
+    let bytes = (number * unit as f64) as u64;
+
+    match sign {
+        Some('+') => Some((Some(bytes), None)),
+        Some(_) => Some((None, Some(bytes))),
+        None => Some((Some(bytes), Some(bytes))),
+    }
+}
+
+
+
+/** a local date "2023-01-01", a date and time "2023-01-01 12:00:00", a duration before now "2d" or a file's modification time */
+pub fn parse_time(arg: &str, now: SystemTime) -> Option<SystemTime> {
+    let date_time = NaiveDateTime::parse_from_str(arg, "%Y-%m-%d %H:%M:%S")
+        .or_else(|_| NaiveDateTime::parse_from_str(arg, "%Y-%m-%dT%H:%M:%S"))
+        .ok()
+        .or_else(|| NaiveDate::parse_from_str(arg, "%Y-%m-%d").ok().map(|date| date.and_hms(0, 0, 0)));
+    if let Some(date_time) = date_time {
+        return Local.from_local_datetime(&date_time).earliest().map(SystemTime::from);
+    }
+
+    if let Some(duration) = parse_duration(arg) {
+        return now.checked_sub(duration);
+    }
+
+    fs::metadata(arg).and_then(|metadata| metadata.modified()).ok()
+}
+
+
+//"90s", "30min", "12h", "2d" or "3w"
+fn parse_duration(arg: &str) -> Option<Duration> {
+    let digits = arg.find(|c: char| !c.is_ascii_digit())?;
+    let number: u64 = arg[..digits].parse().ok()?;
+    let seconds = match &arg[digits..] {
+        "s" => 1,
+        "min" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return None,
+    };
+
+    Some(Duration::from_secs(number.checked_mul(seconds)?))
+}
+
+
+
+fn build_globs<'a>(patterns: impl Iterator<Item = &'a str>) -> Result<Option<GlobSet>, String> {
+    let mut builder = GlobSetBuilder::new();
+    let mut empty = true;
+
+    for pattern in patterns {
+        let pattern = pattern.trim_start_matches("./").trim_end_matches('/');
+        let anchored = match pattern.strip_prefix('/') {
+            Some(anchored) => anchored.to_string(),
+            None if pattern.contains('/') => pattern.to_string(),
+            None => format!("**/{}", pattern),
+        };
+
+        match GlobBuilder::new(&anchored).literal_separator(true).build() {
+            Ok(glob) => builder.add(glob),
+            Err(err) => return Err(format!("Bad glob '{}' provided\n\n {}", pattern, err)),
+        };
+        empty = false;
+    }
+
+    if empty {
+        return Ok(None);
+    }
+
+    match builder.build() {
+        Ok(globs) => Ok(Some(globs)),
+        Err(err) => Err(format!("Bad glob provided\n\n {}", err)),
+    }
+}
+
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+
+    #[test]
+    fn include_and_exclude() {
+        let filter = PathFilter::new(&["*.jpg", "docs/*.md"], &["node_modules/**", "*.tmp.jpg"], None).unwrap();
+
+        assert!(filter.is_selected(Path::new("a/b/photo.jpg")));
+        assert!(filter.is_selected(Path::new("docs/readme.md")));
+        assert!(!filter.is_selected(Path::new("src/docs/readme.md")));
+        assert!(!filter.is_selected(Path::new("notes.txt")));
+
+        assert!(filter.is_excluded(Path::new("node_modules")));
+        assert!(filter.is_excluded(Path::new("node_modules/pkg/logo.jpg")));
+        assert!(!filter.is_excluded(Path::new("src/node_modules")));
+        assert!(filter.is_excluded(Path::new("cache/x.tmp.jpg")));
+    }
+
+
+    #[test]
+    fn where_expression() {
+        let filter = PathFilter::new(&[], &[], Some(r"^2024/\d{2}/")).unwrap();
+
+        assert!(filter.is_selected(Path::new("2024/03/img.png")));
+        assert!(!filter.is_selected(Path::new("2023/03/img.png")));
+        assert!(PathFilter::new(&["a[b"], &[], None).is_err());
+        assert!(PathFilter::new(&[], &[], Some("(")).is_err());
+    }
+
+
+    #[test]
+    fn metadata_predicates() {
+        assert_eq!(parse_size("+10M"), Some((Some(10_000_000), None)));
+        assert_eq!(parse_size("-1.5Ki"), Some((None, Some(1536))));
+        assert_eq!(parse_size("512b"), Some((Some(512), Some(512))));
+        assert_eq!(parse_size("10 parsecs"), None);
+
+        let now = SystemTime::now();
+        assert_eq!(parse_time("2d", now), now.checked_sub(Duration::from_secs(2 * 24 * 60 * 60)));
+        assert!(parse_time("2023-01-01", now).unwrap() < parse_time("2023-01-01 12:00:00", now).unwrap());
+        assert_eq!(parse_time("yesterday", now), None);
+
+        let tempdir = tempfile::tempdir().unwrap();
+        let root = tempdir.path();
+        fs::create_dir(root.join("full")).unwrap();
+        fs::create_dir(root.join("void")).unwrap();
+        fs::write(root.join("full/big.bin"), vec![0; 2048]).unwrap();
+        fs::write(root.join("blank.txt"), "").unwrap();
+
+        let check = |predicates: &Predicates, name: &str| {
+            let path = root.join(name);
+            predicates.matches(&path, &path.symlink_metadata().unwrap())
+        };
+
+        let files = Predicates { types: vec![EntryType::File], min_size: Some(1024), ..Predicates::default() };
+        assert!(check(&files, "full/big.bin"));
+        assert!(!check(&files, "blank.txt"));
+        assert!(!check(&files, "full"));
+
+        let empty = Predicates { empty: true, ..Predicates::default() };
+        assert!(check(&empty, "void"));
+        assert!(check(&empty, "blank.txt"));
+        assert!(!check(&empty, "full"));
+
+        let old = Predicates { older: parse_time("2000-01-01", now), ..Predicates::default() };
+        assert!(!check(&old, "blank.txt"));
+    }
+}
@@ -0,0 +1,233 @@
+//gitignore rules for recursive mode
+//
+// .ignore and .rxignore files are read in every walked directory and in the parents of
+// the root up to the repository, .gitignore files and the global excludes only apply
+// inside a git repository. Later files win over earlier ones and deeper directories
+// over shallower ones, like git does.
+
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::Match;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+
+
+const IGNORE_FILES: [&str; 3] = [".gitignore", ".ignore", ".rxignore"];
+const GIT_IGNORE_FILE: &str = ".gitignore";
+
+
+
+/** cheap to clone, so each directory of a parallel walk can carry the rules above it */
+#[derive(Clone)]
+pub struct IgnoreRules {
+    //walked directories with ignore files, deepest last
+    stack: Vec<Arc<(PathBuf, Gitignore)>>,
+    //parents of the root and .git/info/exclude, most specific first
+    outer: Arc<Vec<Gitignore>>,
+    global: Arc<Gitignore>,
+    //repositories holding the walked directory, deepest last
+    repositories: Vec<PathBuf>,
+}
+
+
+impl IgnoreRules {
+    /** rules applying to an absolute root, before its own ignore files are read */
+    pub fn new(root: &Path) -> IgnoreRules {
+        let mut outer = Vec::new();
+
+        let repository = root.ancestors().find(|dir| is_repository(dir));
+        if let Some(repository) = repository {
+            for parent in root.ancestors().skip(1) {
+                if !parent.starts_with(repository) {
+                    break;
+                }
+
+                outer.extend(directory_rules(parent, true));
+            }
+
+            let (exclude, _) = Gitignore::new(repository.join(".git").join("info").join("exclude"));
+            outer.push(exclude);
+        }
+
+        let (global, _) = Gitignore::global();
+
+        IgnoreRules {
+            stack: Vec::new(),
+            outer: Arc::new(outer),
+            global: Arc::new(global),
+            repositories: repository.map(Path::to_path_buf).into_iter().collect(),
+        }
+    }
+
+
+    /** read the ignore files of a directory about to be walked into */
+    pub fn enter(&mut self, dir: &Path) {
+        self.leave_until(dir);
+
+        if is_repository(dir) && self.repositories.last().map(|repository| repository != dir).unwrap_or(true) {
+            self.repositories.push(dir.to_path_buf());
+        }
+
+        if let Some(rules) = directory_rules(dir, !self.repositories.is_empty()) {
+            self.stack.push(Arc::new((dir.to_path_buf(), rules)));
+        }
+    }
+
+
+    pub fn is_ignored(&mut self, path: &Path, is_dir: bool) -> bool {
+        if let Some(parent) = path.parent() {
+            self.leave_until(parent);
+        }
+
+        let global = if self.repositories.is_empty() { None } else { Some(&*self.global) };
+        let deepest_first = self.stack.iter().rev().map(|level| &level.1).chain(self.outer.iter()).chain(global);
+        for rules in deepest_first {
+            match rules.matched(path, is_dir) {
+                Match::Ignore(_) => return true,
+                Match::Whitelist(_) => return false,
+                Match::None => {}
+            }
+        }
+
+        false
+    }
+
+
+    //drop the rules of the directories the walk came out of
+    fn leave_until(&mut self, dir: &Path) {
+        while let Some(top) = self.stack.last() {
+            if dir.starts_with(&top.0) {
+                break;
+            }
+            self.stack.pop();
+        }
+
+        //a repository found during the walk ends with its directory
+        while self.repositories.last().map(|repository| !dir.starts_with(repository)).unwrap_or(false) {
+            self.repositories.pop();
+        }
+    }
+}
+
+
+
+fn is_repository(dir: &Path) -> bool {
+    dir.join(".git").exists()
+}
+
+
+//like git, .gitignore files outside a repository are left out
+fn directory_rules(dir: &Path, in_repository: bool) -> Option<Gitignore> {
+    let files: Vec<PathBuf> = IGNORE_FILES
+        .iter()
+        .filter(|name| in_repository || **name != GIT_IGNORE_FILE)
+        .map(|name| dir.join(name))
+        .filter(|file| file.is_file())
+        .collect();
+    if files.is_empty() {
+        return None;
+    }
+
+    let mut builder = GitignoreBuilder::new(dir);
+    for file in files {
+        //like git, broken lines are skipped and the rest of the file still applies
+        builder.add(file);
+    }
+
+    builder.build().ok()
+}
+
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::config::{RunMode, WalkRoot};
+    use crate::fileutils::get_paths;
+    use crate::output::Printer;
+    use crate::filter::{PathFilter, Predicates};
+    use std::fs;
+
+
+    #[test]
+    fn nested_ignore_files() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let root = tempdir.path();
+
+        for dir in &[".git", "target/debug", "src/drafts", "logs"] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+        }
+
+        let files = [
+            (".gitignore", "target/\n*.log\n"),
+            ("logs/.gitignore", "!keep.log\n"),
+            ("src/.rxignore", "drafts/\n*.bak\n"),
+            ("src/.ignore", "!main.bak\n"),
+        ];
+        for (file, contents) in &files {
+            fs::write(root.join(file), contents).unwrap();
+        }
+        for file in &["target/debug/rx", "src/main.rs", "src/main.bak", "src/drafts/a.rs", "logs/run.log", "logs/keep.log"] {
+            fs::write(root.join(file), "").unwrap();
+        }
+
+        let walk = |ignore| {
+            let mode = RunMode::Recursive {
+                roots: vec![WalkRoot { path: root.to_string_lossy().to_string(), min_depth: 0, max_depth: None }],
+                hidden: false,
+                follow: false,
+                one_file_system: false,
+                threads: 1,
+                filter: PathFilter::default(),
+                predicates: Predicates::default(),
+                ignore,
+            };
+
+            let mut files: Vec<PathBuf> = get_paths(&mode, &Printer::silent()).unwrap().into_iter().filter(|p| p.is_file()).collect();
+            files.sort();
+            files
+        };
+
+        //.rxignore comes last so it wins over .ignore in the same directory
+        assert_eq!(walk(true), vec![root.join("logs/keep.log"), root.join("src/main.rs")]);
+        assert_eq!(walk(false).len(), 6);
+    }
+
+
+    #[test]
+    fn gitignore_outside_repository() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let root = tempdir.path();
+        if root.ancestors().any(is_repository) {
+            return;
+        }
+
+        for dir in &["plain", "plain/repo/.git", "plain/repo/sub"] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        let files = [
+            ("plain/.gitignore", "*.log\n"),
+            ("plain/.ignore", "*.tmp\n"),
+            ("plain/repo/.gitignore", "*.bak\n"),
+        ];
+        for (file, contents) in &files {
+            fs::write(root.join(file), contents).unwrap();
+        }
+        for file in &["plain/a.log", "plain/a.tmp", "plain/a.bak", "plain/repo/sub/b.log", "plain/repo/sub/b.tmp", "plain/repo/sub/b.bak"] {
+            fs::write(root.join(file), "").unwrap();
+        }
+
+        //the .gitignore above the repository is not part of it, git leaves it out too
+        let mut rules = IgnoreRules::new(root);
+        for dir in &["plain", "plain/repo", "plain/repo/sub"] {
+            rules.enter(&root.join(dir));
+        }
+        assert!(rules.is_ignored(&root.join("plain/repo/sub/b.bak"), false));
+        assert!(rules.is_ignored(&root.join("plain/repo/sub/b.tmp"), false));
+        assert!(!rules.is_ignored(&root.join("plain/repo/sub/b.log"), false));
+
+        assert!(!rules.is_ignored(&root.join("plain/a.log"), false));
+        assert!(rules.is_ignored(&root.join("plain/a.tmp"), false));
+        assert!(!rules.is_ignored(&root.join("plain/a.bak"), false));
+    }
+}
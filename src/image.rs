@@ -0,0 +1,262 @@
+//image dimensions read from the file header
+//
+// {width} and {height}   pixels as displayed, EXIF rotated JPEG files included
+// {orientation}          landscape, portrait or square
+
+use crate::providers::FileCache;
+use crate::template::{check_pad, pad_number, Context, Provider};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+
+
+/** {width}, {height} and {orientation} of PNG, JPEG, GIF and WebP files */
+#[derive(Default)]
+pub struct ImageProvider {
+    cache: FileCache<(u32, u32)>,
+}
+
+
+impl Provider for ImageProvider {
+    fn provides(&self, name: &str) -> bool {
+        ["width", "height", "orientation"].contains(&name)
+    }
+
+    fn check(&self, name: &str, arg: Option<&str>) -> Result<(), String> {
+        match name {
+            "width" | "height" => check_pad(arg),
+            _ if arg.is_some() => Err(format!("'{}' takes no argument", name)),
+            _ => Ok(()),
+        }
+    }
+
+    fn value(&self, name: &str, arg: Option<&str>, context: &Context) -> Option<String> {
+        let (width, height) = self.cache.get(context.path, read_dimensions)?;
+        let number = |value: u32| {
+            let value = value.to_string();
+            arg.map(|spec| pad_number(&value, spec)).unwrap_or(value)
+        };
+
+        match name {
+            "width" => Some(number(width)),
+            "height" => Some(number(height)),
+            "orientation" => Some(String::from(match width.cmp(&height) {
+                std::cmp::Ordering::Greater => "landscape",
+                std::cmp::Ordering::Less => "portrait",
+                std::cmp::Ordering::Equal => "square",
+            })),
+            _ => None,
+        }
+    }
+}
+
+
+
+pub fn read_dimensions(path: &Path) -> Option<(u32, u32)> {
+    let mut file = File::open(path).ok()?;
+    dimensions(&mut file)
+}
+
+
+fn dimensions<R: Read + Seek>(reader: &mut R) -> Option<(u32, u32)> {
+    let mut header = [0; 30];
+    let length = read_up_to(reader, &mut header)?;
+    let header = &header[..length];
+
+    if header.starts_with(b"\x89PNG\r\n\x1a\n") && header.get(12..16) == Some(b"IHDR") {
+        return Some((be_u32(header, 16)?, be_u32(header, 20)?));
+    }
+
+    if header.starts_with(b"GIF87a") || header.starts_with(b"GIF89a") {
+        return Some((u32::from(le_u16(header, 6)?), u32::from(le_u16(header, 8)?)));
+    }
+
+    if header.starts_with(b"RIFF") && header.get(8..12) == Some(b"WEBP") {
+        return webp_dimensions(header);
+    }
+
+    if header.starts_with(&[0xFF, 0xD8]) {
+        reader.seek(SeekFrom::Start(2)).ok()?;
+        return jpeg_dimensions(reader);
+    }
+
+    None
+}
+
+
+fn read_up_to<R: Read>(reader: &mut R, buffer: &mut [u8]) -> Option<usize> {
+    let mut length = 0;
+    while length < buffer.len() {
+        match reader.read(&mut buffer[length..]).ok()? {
+            0 => break,
+            read => length += read,
+        }
+    }
+    Some(length)
+}
+
+
+fn webp_dimensions(header: &[u8]) -> Option<(u32, u32)> {
+    match header.get(12..16)? {
+        //lossy, the frame header follows the 3 bytes start code
+        b"VP8 " => Some((u32::from(le_u16(header, 26)? & 0x3FFF), u32::from(le_u16(header, 28)? & 0x3FFF))),
+        //lossless, 14 bits each minus one
+        b"VP8L" => {
+            let bits = u32::from_le_bytes([*header.get(21)?, *header.get(22)?, *header.get(23)?, *header.get(24)?]);
+            Some(((bits & 0x3FFF) + 1, ((bits >> 14) & 0x3FFF) + 1))
+        }
+        //extended, 24 bits canvas size minus one
+        b"VP8X" => {
+            let width = u32::from_le_bytes([*header.get(24)?, *header.get(25)?, *header.get(26)?, 0]);
+            let height = u32::from_le_bytes([*header.get(27)?, *header.get(28)?, *header.get(29)?, 0]);
+            Some((width + 1, height + 1))
+        }
+        _ => None,
+    }
+}
+
+
+//walk the segments up to the frame header, the EXIF orientation is read on the way
+fn jpeg_dimensions<R: Read + Seek>(reader: &mut R) -> Option<(u32, u32)> {
+    let mut orientation = 1;
+
+    loop {
+        let mut marker = [0; 2];
+        reader.read_exact(&mut marker).ok()?;
+        if marker[0] != 0xFF {
+            return None;
+        }
+
+        //fill bytes and markers without a length
+        match marker[1] {
+            0xFF => {
+                reader.seek(SeekFrom::Current(-1)).ok()?;
+                continue;
+            }
+            0x01 | 0xD0..=0xD7 => continue,
+            0xD9 | 0xDA => return None,
+            _ => {}
+        }
+
+        let mut length = [0; 2];
+        reader.read_exact(&mut length).ok()?;
+        let length = usize::from(u16::from_be_bytes(length)).checked_sub(2)?;
+
+        match marker[1] {
+            //start of frame, except DHT, JPG and DAC that share the range
+            0xC0..=0xCF if ![0xC4, 0xC8, 0xCC].contains(&marker[1]) => {
+                let mut frame = [0; 5];
+                reader.read_exact(&mut frame).ok()?;
+                let height = u32::from(u16::from_be_bytes([frame[1], frame[2]]));
+                let width = u32::from(u16::from_be_bytes([frame[3], frame[4]]));
+
+                //orientations 5 to 8 are rotated by a quarter turn
+                return if (5..=8).contains(&orientation) { Some((height, width)) } else { Some((width, height)) };
+            }
+            0xE1 => {
+                let mut segment = vec![0; length];
+                reader.read_exact(&mut segment).ok()?;
+                if let Some(value) = exif_orientation(&segment) {
+                    orientation = value;
+                }
+            }
+            _ => {
+                reader.seek(SeekFrom::Current(length as i64)).ok()?;
+            }
+        }
+    }
+}
+
+
+//tag 0x0112 of the first IFD of an "Exif\0\0" APP1 segment
+fn exif_orientation(segment: &[u8]) -> Option<u16> {
+    let tiff = segment.strip_prefix(b"Exif\0\0")?;
+    let little_endian = match tiff.get(..2)? {
+        b"II" => true,
+        b"MM" => false,
+        _ => return None,
+    };
+
+    let u16_at = |position: usize| {
+        let bytes = [*tiff.get(position)?, *tiff.get(position + 1)?];
+        Some(if little_endian { u16::from_le_bytes(bytes) } else { u16::from_be_bytes(bytes) })
+    };
+    let u32_at = |position: usize| {
+        let bytes = [*tiff.get(position)?, *tiff.get(position + 1)?, *tiff.get(position + 2)?, *tiff.get(position + 3)?];
+        Some(if little_endian { u32::from_le_bytes(bytes) } else { u32::from_be_bytes(bytes) })
+    };
+
+    let ifd = u32_at(4)? as usize;
+    let entries = u16_at(ifd)?;
+
+    (0..usize::from(entries))
+        .map(|index| ifd + 2 + index * 12)
+        .find(|entry| u16_at(*entry) == Some(0x0112))
+        .and_then(|entry| u16_at(entry + 8))
+}
+
+
+fn be_u32(data: &[u8], position: usize) -> Option<u32> {
+    let bytes = data.get(position..position + 4)?;
+    Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+
+fn le_u16(data: &[u8], position: usize) -> Option<u16> {
+    let bytes = data.get(position..position + 2)?;
+    Some(u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Cursor;
+
+
+    fn read(data: Vec<u8>) -> Option<(u32, u32)> {
+        dimensions(&mut Cursor::new(data))
+    }
+
+
+    #[test]
+    fn png_gif_and_webp_headers() {
+        let mut png = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+        png.extend_from_slice(&1920u32.to_be_bytes());
+        png.extend_from_slice(&1080u32.to_be_bytes());
+        assert_eq!(read(png), Some((1920, 1080)));
+
+        assert_eq!(read(b"GIF89a\x40\x01\xf0\x00".to_vec()), Some((320, 240)));
+
+        let mut lossy = b"RIFF\0\0\0\0WEBPVP8 \0\0\0\0\0\0\0\x9d\x01\x2a".to_vec();
+        lossy.extend_from_slice(&[0x20, 0x03, 0x58, 0x02]);
+        assert_eq!(read(lossy), Some((800, 600)));
+
+        let mut lossless = b"RIFF\0\0\0\0WEBPVP8L\0\0\0\0\x2f".to_vec();
+        lossless.extend_from_slice(&(399u32 | (299 << 14)).to_le_bytes());
+        assert_eq!(read(lossless), Some((400, 300)));
+
+        assert_eq!(read(b"plain text file".to_vec()), None);
+    }
+
+
+    #[test]
+    fn jpeg_frame_and_exif_rotation() {
+        let frame = [0xFF, 0xC0, 0x00, 0x11, 0x08, 0x0F, 0xC0, 0x0B, 0xD0];
+        let mut jpeg = vec![0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x04, 0x00, 0x00];
+        jpeg.extend_from_slice(&frame);
+        assert_eq!(read(jpeg), Some((3024, 4032)));
+
+        //big endian EXIF with a single orientation entry set to 6
+        let mut exif = b"Exif\0\0MM\0\x2a\0\0\0\x08\0\x01".to_vec();
+        exif.extend_from_slice(&[0x01, 0x12, 0x00, 0x03, 0x00, 0x00, 0x00, 0x01, 0x00, 0x06, 0x00, 0x00]);
+
+        let mut rotated = vec![0xFF, 0xD8, 0xFF, 0xE1];
+        rotated.extend_from_slice(&((exif.len() + 2) as u16).to_be_bytes());
+        rotated.extend(exif);
+        rotated.extend_from_slice(&frame);
+        assert_eq!(read(rotated), Some((4032, 3024)));
+    }
+}
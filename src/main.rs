@@ -19,16 +19,33 @@ extern crate clap;
 #[macro_use]
 extern crate serde_derive;
 
+#[macro_use]
+extern crate lazy_static;
+
 
 
+mod csvfile;
 mod dumpfile;
+mod editor;
 mod config;
+mod dates;
+mod documents;
 mod app;
 mod error; 
 mod output;
 mod fileutils;
+mod ignores;
+mod filter;
+mod image;
+mod media;
+mod providers;
 mod renamer;
+mod sequence;
 mod solver;
+mod template;
+mod translit;
+mod video;
+mod walker;
 
 
 
@@ -0,0 +1,193 @@
+//TV and movie release name recognizer
+
+use crate::template::{check_pad, pad_number, Context, Provider};
+use regex::Regex;
+
+
+
+#[derive(Debug, PartialEq)]
+pub struct MediaInfo {
+    pub show: String,
+    pub season: Option<u32>,
+    pub episode: Option<u32>,
+    pub year: Option<u32>,
+    pub resolution: Option<String>,
+}
+
+
+
+pub struct MediaParser {
+    episode_patterns: Vec<Regex>,
+    year: Regex,
+    resolution: Regex,
+    group: Regex,
+}
+
+
+impl MediaParser {
+    pub fn new() -> MediaParser {
+        let episode_patterns = [
+            r"(?i)\bS(\d{1,2})[ ]?E(\d{1,3})\b",   //S01E02, s01e02, S01.E02
+            r"(?i)\b(\d{1,2})x(\d{2,3})\b",        //1x02
+            r"(?i)\bSeason (\d{1,2}) Episode (\d{1,3})\b",
+        ];
+
+        MediaParser {
+            episode_patterns: episode_patterns.iter().map(|p| Regex::new(p).unwrap()).collect(),
+            year: Regex::new(r"\(?\b((?:19|20)\d{2})\b\)?").unwrap(),
+            resolution: Regex::new(r"(?i)\b(2160p|1080p|1080i|720p|576p|480p|4k|uhd)\b").unwrap(),
+            group: Regex::new(r"^\s*\[[^\]]*\]").unwrap(),
+        }
+    }
+
+
+    /** parse a release name, None when it has neither an episode nor a year */
+    pub fn parse(&self, name: &str) -> Option<MediaInfo> {
+        //dots and underscores are word separators in release names
+        let normalized = self.group.replace(name, "").replace(['.', '_'], " ");
+
+        let mut show_end = normalized.len();
+        let mut season = None;
+        let mut episode = None;
+
+        if let Some(captures) = self.episode_patterns.iter().find_map(|p| p.captures(&normalized)) {
+            show_end = captures.get(0).unwrap().start();
+            season = captures[1].parse::<u32>().ok();
+            episode = captures[2].parse::<u32>().ok();
+        }
+
+        //the first year after the show name, "2001 A Space Odyssey 1968" keeps its title
+        let year_match = self.year.captures_iter(&normalized).find(|c| c.get(0).unwrap().start() > 0);
+        let year = year_match.as_ref().and_then(|c| c[1].parse::<u32>().ok());
+        if let Some(captures) = &year_match {
+            show_end = show_end.min(captures.get(0).unwrap().start());
+        }
+
+        let resolution = self.resolution.find(&normalized).map(|m| {
+            show_end = show_end.min(m.start());
+            match m.as_str().to_lowercase().as_str() {
+                "4k" | "uhd" => String::from("2160p"),
+                other => other.to_string(),
+            }
+        });
+
+        if episode.is_none() && year.is_none() {
+            return None;
+        }
+
+        let show = normalized[..show_end]
+            .split_whitespace()
+            .collect::<Vec<&str>>()
+            .join(" ")
+            .trim_end_matches(|c: char| c == '-' || c == '(' || c == '[' || c.is_whitespace())
+            .to_string();
+
+        if show.is_empty() {
+            return None;
+        }
+
+        Some(MediaInfo { show, season, episode, year, resolution })
+    }
+}
+
+
+
+impl Default for MediaParser {
+    fn default() -> Self {
+        MediaParser::new()
+    }
+}
+
+
+
+/** {show}, {season}, {episode}, {year} and {resolution} of release names */
+pub struct MediaProvider {
+    parser: MediaParser,
+}
+
+
+impl MediaProvider {
+    pub fn new() -> MediaProvider {
+        MediaProvider { parser: MediaParser::new() }
+    }
+}
+
+
+impl Provider for MediaProvider {
+    fn provides(&self, name: &str) -> bool {
+        ["show", "season", "episode", "year", "resolution"].contains(&name)
+    }
+
+    fn check(&self, name: &str, arg: Option<&str>) -> Result<(), String> {
+        match name {
+            "season" | "episode" => check_pad(arg),
+            _ if arg.is_some() => Err(format!("'{}' takes no argument", name)),
+            _ => Ok(()),
+        }
+    }
+
+    fn value(&self, name: &str, arg: Option<&str>, context: &Context) -> Option<String> {
+        let stem = context.path.file_stem()?.to_string_lossy();
+        let info = self.parser.parse(&stem)?;
+
+        let number = |value: Option<u32>| {
+            let value = value?.to_string();
+            Some(arg.map(|spec| pad_number(&value, spec)).unwrap_or(value))
+        };
+
+        match name {
+            "show" => Some(info.show),
+            "season" => number(info.season),
+            "episode" => number(info.episode),
+            "year" => info.year.map(|y| y.to_string()),
+            "resolution" => info.resolution,
+            _ => None,
+        }
+    }
+}
+
+
+
+impl Default for MediaProvider {
+    fn default() -> Self {
+        MediaProvider::new()
+    }
+}
+
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+
+    #[test]
+    fn parse_episode_notations() {
+        let parser = MediaParser::new();
+
+        for name in &["The.Show.s01e02.720p.WEB", "The Show - 1x02", "The_Show.S01.E02.HDTV", "[Group] The Show S01E02"] {
+            let info = parser.parse(name).unwrap();
+            assert_eq!(info.show, "The Show", "{}", name);
+            assert_eq!((info.season, info.episode), (Some(1), Some(2)), "{}", name);
+        }
+
+        let info = parser.parse("Doctor.Who.2005.S10E01.1080p.BluRay").unwrap();
+        assert_eq!(info.show, "Doctor Who");
+        assert_eq!(info.year, Some(2005));
+        assert_eq!(info.resolution, Some(String::from("1080p")));
+    }
+
+
+    #[test]
+    fn parse_movies() {
+        let parser = MediaParser::new();
+
+        let info = parser.parse("2001.A.Space.Odyssey.1968.4K.Remux").unwrap();
+        assert_eq!(info.show, "2001 A Space Odyssey");
+        assert_eq!((info.year, info.episode), (Some(1968), None));
+        assert_eq!(info.resolution, Some(String::from("2160p")));
+
+        assert_eq!(parser.parse("Movie Title (1999)").unwrap().show, "Movie Title");
+        assert_eq!(parser.parse("holiday video"), None);
+    }
+}
@@ -120,6 +120,19 @@ impl Printer {
     }
 
 
+    pub fn print_warning(&self, error: &Error) {
+        let error_value = error.value.to_owned().unwrap_or_else(|| String::from("")); 
+
+
+        self.eprint(&format!(
+            "{}{} {}", 
+            self.colors.warn.paint("Warning: "), 
+            error.description(), 
+            self.colors.warn.paint(error_value)
+        )); 
+    }
+
+
     pub fn print_operation(&self, source: &Path, target: &Path) {
         if self.mode == PrinterMode::Silent {
             return ;
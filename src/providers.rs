@@ -0,0 +1,508 @@
+//template variable providers
+
+use crate::fileutils::absolute_path;
+use crate::template::{check_pad, pad_number, Context, Provider};
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Local};
+use regex::Regex;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+
+
+/** regex captures, {0} is the whole match, {1}.. and named groups */
+pub struct CaptureProvider {
+    names: Vec<String>,
+    count: usize,
+}
+
+
+impl CaptureProvider {
+    pub fn new(expression: &Regex) -> CaptureProvider {
+        CaptureProvider {
+            names: expression.capture_names().flatten().map(String::from).collect(),
+            count: expression.captures_len(),
+        }
+    }
+}
+
+
+impl Provider for CaptureProvider {
+    fn provides(&self, name: &str) -> bool {
+        match name.parse::<usize>() {
+            Ok(index) => index < self.count,
+            Err(_) => self.names.iter().any(|n| n == name),
+        }
+    }
+
+    fn check(&self, _name: &str, arg: Option<&str>) -> Result<(), String> {
+        check_pad(arg)
+    }
+
+    fn value(&self, name: &str, arg: Option<&str>, context: &Context) -> Option<String> {
+        let captures = context.captures?;
+        let capture = match name.parse::<usize>() {
+            Ok(index) => captures.get(index),
+            Err(_) => captures.name(name),
+        }?;
+
+        match arg {
+            Some(spec) => Some(pad_number(capture.as_str(), spec)),
+            None => Some(capture.as_str().to_string()),
+        }
+    }
+}
+
+
+
+/** file name parts and filesystem metadata */
+pub struct MetadataProvider;
+
+
+impl Provider for MetadataProvider {
+    fn provides(&self, name: &str) -> bool {
+        ["name", "stem", "ext", "size", "mtime"].contains(&name)
+    }
+
+    fn check(&self, name: &str, arg: Option<&str>) -> Result<(), String> {
+        match (name, arg) {
+            (_, None) => Ok(()),
+            ("mtime", Some(format)) => {
+                if StrftimeItems::new(format).any(|item| item == Item::Error) {
+                    Err(format!("invalid date format '{}'", format))
+                } else {
+                    Ok(())
+                }
+            }
+            ("size", Some(spec)) => check_pad(Some(spec)),
+            (_, Some(_)) => Err(format!("'{}' takes no argument", name)),
+        }
+    }
+
+    fn value(&self, name: &str, arg: Option<&str>, context: &Context) -> Option<String> {
+        let path = context.path;
+
+        match name {
+            "name" => path.file_name().map(|n| n.to_string_lossy().to_string()),
+            "stem" => path.file_stem().map(|s| s.to_string_lossy().to_string()),
+            "ext" => path.extension().map(|e| e.to_string_lossy().to_string()),
+            "size" => {
+                let size = path.metadata().ok()?.len().to_string();
+                Some(arg.map(|spec| pad_number(&size, spec)).unwrap_or(size))
+            }
+            "mtime" => {
+                let modified: DateTime<Local> = path.metadata().ok()?.modified().ok()?.into();
+                Some(modified.format(arg.unwrap_or("%Y-%m-%d")).to_string())
+            }
+            _ => None,
+        }
+    }
+}
+
+
+
+/** {n} counter of the renamed paths, starting at 1 */
+pub struct CounterProvider;
+
+
+impl Provider for CounterProvider {
+    fn provides(&self, name: &str) -> bool {
+        name == "n"
+    }
+
+    fn check(&self, _name: &str, arg: Option<&str>) -> Result<(), String> {
+        check_pad(arg)
+    }
+
+    fn value(&self, _name: &str, arg: Option<&str>, context: &Context) -> Option<String> {
+        let counter = (context.index + 1).to_string();
+        Some(arg.map(|spec| pad_number(&counter, spec)).unwrap_or(counter))
+    }
+}
+
+
+
+/** {parent}, {parent:N} ancestors and {relpath} relative to the run root */
+pub struct PathProvider;
+
+
+impl PathProvider {
+    //names of the directories between the path and the root, root included
+    fn ancestors(context: &Context) -> Option<Vec<String>> {
+        let path = absolute(context.path)?;
+        let root = absolute(context.root)?;
+
+        let mut ancestors = Vec::new();
+        for ancestor in path.ancestors().skip(1) {
+            if !ancestor.starts_with(&root) {
+                break;
+            }
+
+            ancestors.push(ancestor.file_name()?.to_string_lossy().to_string());
+        }
+
+        Some(ancestors)
+    }
+
+
+    fn relative_path(context: &Context) -> Option<Vec<String>> {
+        let path = absolute(context.path)?;
+        let root = absolute(context.root)?;
+
+        Some(
+            path.strip_prefix(&root)
+                .ok()?
+                .components()
+                .filter_map(|c| match c {
+                    Component::Normal(name) => Some(name.to_string_lossy().to_string()),
+                    _ => None,
+                })
+                .collect(),
+        )
+    }
+}
+
+
+//relative roots like "." are resolved from the working directory
+fn absolute(path: &Path) -> Option<PathBuf> {
+    absolute_path(path).ok()
+}
+
+
+impl Provider for PathProvider {
+    fn provides(&self, name: &str) -> bool {
+        name == "parent" || name == "relpath"
+    }
+
+    fn check(&self, name: &str, arg: Option<&str>) -> Result<(), String> {
+        match (name, arg) {
+            ("parent", Some(level)) => match level.parse::<usize>() {
+                Ok(level) if level > 0 => Ok(()),
+                _ => Err(format!("invalid level '{}', expected 1 for the parent, 2 for the grandparent..", level)),
+            },
+            ("relpath", Some(separator)) if separator.contains('/') || separator.contains('\\') => {
+                Err(String::from("the separator cannot contain a path separator"))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn value(&self, name: &str, arg: Option<&str>, context: &Context) -> Option<String> {
+        match name {
+            "parent" => {
+                let level = arg.and_then(|l| l.parse::<usize>().ok()).unwrap_or(1);
+                PathProvider::ancestors(context)?.into_iter().nth(level - 1)
+            }
+            "relpath" => Some(PathProvider::relative_path(context)?.join(arg.unwrap_or("_"))),
+            _ => None,
+        }
+    }
+}
+
+
+
+/** {uuid}, {uuid:v7}, {ulid} and {rand:N} random ids */
+pub struct RandomProvider {
+    seed: u64,
+    seeded: bool,
+}
+
+
+const CROCKFORD: &[u8] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+
+impl RandomProvider {
+    //without a seed every run gets a different one
+    pub fn new(seed: Option<u64>) -> RandomProvider {
+        match seed {
+            Some(seed) => RandomProvider { seed, seeded: true },
+            None => RandomProvider {
+                seed: RandomState::new().build_hasher().finish(),
+                seeded: false,
+            },
+        }
+    }
+
+
+    //the same seed, path and token always give the same bytes
+    fn random_bytes(&self, name: &str, context: &Context, length: usize) -> Vec<u8> {
+        let mut state = fnv1a(self.seed, context.path.to_string_lossy().as_bytes());
+        state = fnv1a(state, name.as_bytes());
+
+        let mut bytes = Vec::with_capacity(length + 8);
+        while bytes.len() < length {
+            bytes.extend_from_slice(&splitmix64(&mut state).to_be_bytes());
+        }
+
+        bytes.truncate(length);
+        bytes
+    }
+
+
+    //seeded runs use the file mtime, so dry-run and force agree
+    fn timestamp_millis(&self, context: &Context) -> u64 {
+        let time = if self.seeded {
+            context.path.symlink_metadata().and_then(|m| m.modified()).unwrap_or(UNIX_EPOCH)
+        } else {
+            SystemTime::now()
+        };
+
+        time.duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or_default()
+    }
+
+
+    fn uuid(&self, version: &str, context: &Context) -> String {
+        let mut bytes = self.random_bytes(&format!("uuid:{}", version), context, 16);
+
+        if version == "v7" {
+            let timestamp = self.timestamp_millis(context).to_be_bytes();
+            bytes[..6].copy_from_slice(&timestamp[2..]);
+            bytes[6] = 0x70 | (bytes[6] & 0x0f);
+        } else {
+            bytes[6] = 0x40 | (bytes[6] & 0x0f);
+        }
+        bytes[8] = 0x80 | (bytes[8] & 0x3f);
+
+        let hex = to_hex(&bytes);
+        format!("{}-{}-{}-{}-{}", &hex[..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..])
+    }
+
+
+    //48 bits timestamp and 80 random bits in Crockford base32
+    fn ulid(&self, context: &Context) -> String {
+        let random = self.random_bytes("ulid", context, 10);
+        let mut value = (self.timestamp_millis(context) as u128 & 0xffff_ffff_ffff) << 80;
+        for (index, byte) in random.iter().enumerate() {
+            value |= (*byte as u128) << (72 - index * 8);
+        }
+
+        (0..26)
+            .rev()
+            .map(|index| CROCKFORD[((value >> (index * 5)) & 0x1f) as usize] as char)
+            .collect()
+    }
+}
+
+
+impl Provider for RandomProvider {
+    fn provides(&self, name: &str) -> bool {
+        ["uuid", "ulid", "rand"].contains(&name)
+    }
+
+    fn check(&self, name: &str, arg: Option<&str>) -> Result<(), String> {
+        match (name, arg) {
+            (_, None) => Ok(()),
+            ("uuid", Some("v4")) | ("uuid", Some("v7")) => Ok(()),
+            ("uuid", Some(version)) => Err(format!("unknown uuid version '{}', expected 'v4' or 'v7'", version)),
+            ("rand", Some(length)) => match length.parse::<usize>() {
+                Ok(length) if length > 0 && length <= 64 => Ok(()),
+                _ => Err(format!("invalid length '{}', expected 1 to 64", length)),
+            },
+            (_, Some(_)) => Err(format!("'{}' takes no argument", name)),
+        }
+    }
+
+    fn value(&self, name: &str, arg: Option<&str>, context: &Context) -> Option<String> {
+        match name {
+            "uuid" => Some(self.uuid(arg.unwrap_or("v4"), context)),
+            "ulid" => Some(self.ulid(context)),
+            "rand" => {
+                let length = arg.and_then(|l| l.parse::<usize>().ok()).unwrap_or(8);
+                let hex = to_hex(&self.random_bytes(&format!("rand:{}", length), context, length.div_ceil(2)));
+                Some(hex[..length].to_string())
+            }
+            _ => None,
+        }
+    }
+}
+
+
+fn fnv1a(mut hash: u64, bytes: &[u8]) -> u64 {
+    hash ^= 0xcbf2_9ce4_8422_2325;
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+
+    hash
+}
+
+
+fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+
+
+/** what was read from the last file, templates usually ask for several values of it */
+pub struct FileCache<T>(Mutex<Option<(PathBuf, Option<T>)>>);
+
+
+impl<T: Clone> FileCache<T> {
+    pub fn get(&self, path: &Path, read: fn(&Path) -> Option<T>) -> Option<T> {
+        let mut cache = self.0.lock().ok()?;
+
+        match cache.as_ref() {
+            Some((cached, value)) if cached == path => value.clone(),
+            _ => {
+                let value = read(path);
+                *cache = Some((path.to_path_buf(), value.clone()));
+                value
+            }
+        }
+    }
+}
+
+
+impl<T> Default for FileCache<T> {
+    fn default() -> Self {
+        FileCache(Mutex::new(None))
+    }
+}
+
+
+
+/** check a "key" or "key:FORMAT" argument, only the date keys take a format */
+pub fn check_key(arg: Option<&str>, keys: &[&str], dates: &[&str]) -> Result<(), String> {
+    let arg = arg.ok_or_else(|| format!("expected a key, one of {}", keys.join(", ")))?;
+    let (key, format) = split_key(arg);
+
+    if !keys.contains(&key) {
+        return Err(format!("unknown key '{}', expected one of {}", key, keys.join(", ")));
+    }
+
+    match format {
+        Some(_) if !dates.contains(&key) => Err(format!("'{}' takes no date format", key)),
+        Some(format) if StrftimeItems::new(format).any(|item| item == Item::Error) => {
+            Err(format!("invalid date format '{}'", format))
+        }
+        _ => Ok(()),
+    }
+}
+
+
+pub fn split_key(arg: &str) -> (&str, Option<&str>) {
+    match arg.find(':') {
+        Some(index) => (&arg[..index], Some(&arg[index + 1..])),
+        None => (arg, None),
+    }
+}
+
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::template::Template;
+    use std::path::Path;
+
+
+    #[test]
+    fn render_captures_metadata_and_counter() {
+        let expression = Regex::new(r"^(?P<artist>.+) - (\d+)\.mp3$").unwrap();
+        let providers: Vec<Box<dyn Provider>> = vec![
+            Box::new(CaptureProvider::new(&expression)),
+            Box::new(MetadataProvider),
+            Box::new(CounterProvider),
+        ];
+
+        let template = Template::compile("{artist}/{2:03}-{n:02}.{ext}", providers).unwrap();
+        let captures = expression.captures("Band - 7.mp3").unwrap();
+        let context = Context {
+            path: Path::new("music/Band - 7.mp3"),
+            root: Path::new("music"),
+            captures: Some(&captures),
+            index: 4,
+        };
+
+        assert_eq!(template.render(&context).unwrap(), "Band/007-05.mp3");
+    }
+
+
+    #[test]
+    fn parent_and_relative_path() {
+        let tempdir = tempfile::tempdir().expect("Error creating temp directory");
+        let root = tempdir.path().join("customer");
+        let path = root.join("scans").join("IMG_0001.jpg");
+
+        let providers: Vec<Box<dyn Provider>> = vec![Box::new(PathProvider)];
+        let template = Template::compile("{parent}/{parent:2}/{parent:3|none}/{relpath}/{relpath:-}", providers).unwrap();
+        let context = Context { path: &path, root: &root, captures: None, index: 0 };
+
+        assert_eq!(
+            template.render(&context).unwrap(),
+            "scans/customer/none/scans_IMG_0001.jpg/scans-IMG_0001.jpg"
+        );
+    }
+
+
+    #[test]
+    fn relative_roots() {
+        let render = |path, root| {
+            let providers: Vec<Box<dyn Provider>> = vec![Box::new(PathProvider)];
+            let template = Template::compile("{parent}|{relpath:-}", providers).unwrap();
+            let context = Context { path: Path::new(path), root: Path::new(root), captures: None, index: 0 };
+            template.render(&context).unwrap()
+        };
+
+        let cwd = std::env::current_dir().unwrap();
+        let cwd_name = cwd.file_name().unwrap().to_string_lossy();
+
+        assert_eq!(render("./scans/IMG_0001.jpg", "."), "scans|scans-IMG_0001.jpg");
+        assert_eq!(render("IMG_0001.jpg", "."), format!("{}|IMG_0001.jpg", cwd_name));
+        assert_eq!(render("./sub/scans/IMG_0001.jpg", "./sub"), "scans|scans-IMG_0001.jpg");
+        assert_eq!(render("./sub/IMG_0001.jpg", "./sub"), "sub|IMG_0001.jpg");
+    }
+
+
+    #[test]
+    fn seeded_random_ids_are_stable() {
+        let render = |seed, path| {
+            let providers: Vec<Box<dyn Provider>> = vec![Box::new(RandomProvider::new(seed))];
+            let template = Template::compile("{uuid} {uuid:v7} {ulid} {rand:5}", providers).unwrap();
+            let context = Context { path: Path::new(path), root: Path::new("missing"), captures: None, index: 0 };
+            template.render(&context).unwrap()
+        };
+
+        let rendered = render(Some(42), "missing/file.txt");
+        assert_eq!(rendered, render(Some(42), "missing/file.txt"));
+        assert_ne!(rendered, render(Some(42), "missing/other.txt"));
+        assert_ne!(rendered, render(Some(7), "missing/file.txt"));
+
+        let parts: Vec<&str> = rendered.split(' ').collect();
+        assert_eq!(parts[0].len(), 36);
+        assert_eq!(&parts[0][14..15], "4");
+        assert!(parts[1].starts_with("00000000-0000-7"));
+        assert!(parts[2].starts_with("0000000000") && parts[2].len() == 26);
+        assert_eq!(parts[3].len(), 5);
+    }
+
+
+    #[test]
+    fn unknown_capture_and_bad_argument() {
+        let expression = Regex::new(r"(a)").unwrap();
+        let compile = |template| {
+            let providers: Vec<Box<dyn Provider>> = vec![
+                Box::new(CaptureProvider::new(&expression)),
+                Box::new(MetadataProvider),
+            ];
+            Template::compile(template, providers).err().unwrap()
+        };
+
+        assert_eq!(compile("{2}").message, "unknown variable '2'");
+        assert_eq!(compile("{stem:x}").message, "'stem' takes no argument");
+        assert_eq!(compile("{mtime:%Q}").column, 8);
+    }
+}
@@ -1,22 +1,37 @@
 
-use any_ascii::any_ascii;
-use crate::config::{Config, ReplaceMode, RunMode};
-use crate::dumpfile::{ Operation, Operations, self};
+use regex::Captures;
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use crate::config::{Config, ReplaceMode, RunMode, SymlinkMode};
+use crate::csvfile;
+use crate::dumpfile::{ Operation, Operations, Relink, Relinks, self};
+use crate::editor;
 use crate::error::*;
-use crate::fileutils::{create_backup, get_paths, };
+use crate::fileutils::{absolute_path, create_backup, create_symlink, get_paths, get_root, normalize_path, relative_path, sort_paths};
+use crate::sequence;
 use crate::solver;
-use std::collections::HashMap;
+use crate::template::{Context, Template};
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, HashSet};
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 
 
-pub type RenameMap = HashMap<PathBuf, PathBuf>; 
+//sorted by target, so the solver output does not depend on hashing
+pub type RenameMap = BTreeMap<PathBuf, PathBuf>; 
+
+
+//everything but RFC 3986 unreserved characters gets encoded
+const URL_ENCODE_SET: &AsciiSet = &NON_ALPHANUMERIC.remove(b'-').remove(b'.').remove(b'_').remove(b'~'); 
 
 
 pub struct Renamer {
     config: Arc<Config>, 
+    counter: Cell<usize>, 
+    transliterated: RefCell<BTreeMap<char, String>>, 
+    //symlinks to point at their renamed target once the renames are done, dumped along with the operations
+    relinks: RefCell<Relinks>, 
 }
 
 
@@ -29,6 +44,9 @@ impl Renamer {
     pub fn new(config: &Arc<Config>) -> Result<Renamer>{
         Ok(Renamer {
             config: config.clone(), 
+            counter: Cell::new(0), 
+            transliterated: RefCell::new(BTreeMap::new()), 
+            relinks: RefCell::new(Vec::new()), 
         })
     }
 
@@ -36,34 +54,58 @@ impl Renamer {
 /** process user input accordingly */
     pub fn process (&self) -> Result<Operations>{
         let operations = match self.config.run_mode {
-            RunMode::Simple(_) | RunMode::Recursive { .. } => {
+            RunMode::Simple(_) | RunMode::Recursive { .. } | RunMode::Stdin { .. } => {
                 //get user input path
 
-                let input_paths = get_paths(&self.config.run_mode);
-                let rename_map = self.get_rename_map(&input_paths)?;
+                let mut input_paths = get_paths(&self.config.run_mode, &self.config.printer)?;
+                sort_paths(&mut input_paths, self.config.sort, self.config.reverse);
+                let (rename_map, links) = match self.config.replace_mode {
+                    ReplaceMode::Editor => (editor::edit_rename_map(&input_paths)?, Vec::new()),
+                    ReplaceMode::Sequence(ref renumber) => (sequence::rename_map(&input_paths, renumber)?, Vec::new()),
+                    _ => self.get_rename_map(&input_paths)?,
+                };
+                self.check_roots(&rename_map)?; 
+
+                self.print_transliterated(); 
 
         
                 //solve renaming option  ordering to avoid conflict; 
-                solver::solve_rename_order(&rename_map)? 
+                let operations = solver::solve_rename_order(&rename_map)?; 
+                *self.relinks.borrow_mut() = get_relinks(&rename_map, &links)?; 
+                operations
             }
 
             RunMode::FromFile { ref path, undo} => {
                 //read operation from file
-                let operations = dumpfile::read_from_file(&PathBuf::from(path))?; 
+                let (operations, relinks) = dumpfile::read_from_file(&PathBuf::from(path))?; 
 
                 if undo {
+                    *self.relinks.borrow_mut() = solver::revert_relinks(&relinks); 
                     solver::revert_operations(&operations)? 
 
                 }else {
+                    *self.relinks.borrow_mut() = relinks; 
                     operations
                 }
 
             }
+
+            RunMode::FromCsv { ref path, ref base, delimiter, header } => {
+                //read mappings from a spreadsheet export
+                let rename_map = csvfile::read_rename_map(
+                    &PathBuf::from(path), 
+                    &PathBuf::from(base), 
+                    delimiter, 
+                    header
+                )?; 
+
+                solver::solve_rename_order(&rename_map)?
+            }
         };
 
 
         if self.config.dump {
-            dumpfile::dump_to_file(&operations)?; 
+            dumpfile::dump_to_file(&operations, &self.relinks.borrow())?; 
         }
 
         Ok(operations)
@@ -76,28 +118,167 @@ impl Renamer {
             self.rename(&operation)?; 
         }
 
+        for relink in self.relinks.borrow().iter() {
+            self.relink(relink)?; 
+        }
+
+        Ok(())
+    }
+
+
+    //point a symlink at the new path of its target, dry runs leave it as is
+    fn relink(&self, relink: &Relink) -> Result<()> {
+        let printer = &self.config.printer; 
+        let colors = &printer.colors; 
+        let (link, contents) = (&relink.target, &relink.new_contents); 
+
+        if !self.config.force {
+            return Ok(()); 
+        }
+
+        //a link changed since its contents were planned is left alone
+        if fs::read_link(link).ok().as_ref() != Some(&relink.old_contents) {
+            printer.print_warning(&invalid_name(link, "the link changed, it is not updated")); 
+            return Ok(()); 
+        }
+
+        if fs::remove_file(link).is_err() {
+            return Err(Error {
+                kind: ErrorKind::CreateSymlink, 
+                value: Some(link.to_string_lossy().to_string()), 
+            }); 
+        }
+        create_symlink(contents, link)?; 
+
+        printer.print(&format!(
+            "{} Symlink updated - {}", 
+            colors.info.paint("Info: "), 
+            colors.source.paint(format!("{} -> {}", link.display(), contents.display()))
+        )); 
         Ok(())
     }
 
 
     //replace file name matches the given config
-    fn replace_match(&self, path: &Path) -> PathBuf {
-        let file_name = path.file_name().unwrap().to_str().unwrap(); 
+    fn replace_match(&self, path: &Path) -> Result<PathBuf> {
+        //a root like "." or ".." has no name of its own to replace
+        let file_name = match path.file_name() {
+            Some(file_name) => file_name.to_str().ok_or_else(|| invalid_name(path, "the name is not valid UTF-8"))?, 
+            None => return Ok(path.to_path_buf()), 
+        }; 
         //replace match
         let parent = path.parent(); 
-        let target_name =  match &self.config.replace_mode {
+        let target_name = match self.replace_name(&self.config.replace_mode, path, file_name)? {
+            Some(name) => name, 
+            None => return Ok(path.to_path_buf()), //not selected by the replace mode
+        }; 
+
+        match parent {
+            None => Ok(PathBuf::from(target_name)), 
+            Some(path) => Ok(path.join(Path::new(&target_name)))
+        }
+    }
+
+
+    //apply a replace mode to a name, None when the mode leaves the path untouched
+    fn replace_name(&self, replace_mode: &ReplaceMode, path: &Path, file_name: &str) -> Result<Option<String>> {
+        let target_name =  match replace_mode {
             ReplaceMode::RegExp { 
                 expression, 
                 replacement, 
                 limit 
-            } => expression.replacen(file_name, *limit, &replacement[..]).to_string(), 
-                ReplaceMode::ToASCII => any_ascii(file_name), //translate string -> ascii
+            } => {
+                if !expression.is_match(file_name) {
+                    return Ok(None); 
+                }
+
+                expression.replacen(file_name, *limit, &replacement[..]).to_string()
+            }
+                ReplaceMode::ToASCII(transliterator) => {
+                    //translate string -> ascii
+                    let (ascii, replaced) = transliterator.apply(file_name); 
+                    self.transliterated.borrow_mut().extend(replaced); 
+                    ascii
+                }
+                ReplaceMode::Editor => file_name.to_string(), //names come from the editor
+                ReplaceMode::Sequence(_) => file_name.to_string(), //sequences are renumbered as a whole
+                ReplaceMode::Template { expression, template } => {
+                    //names not matching the expression are left untouched
+                    let captures = match expression.captures(file_name) {
+                        Some(captures) => captures, 
+                        None => return Ok(None), 
+                    }; 
+
+                    self.render_template(template, path, Some(&captures))?
+                }
+                ReplaceMode::Media { parser, template } => {
+                    let stem = path.file_stem().map(|s| s.to_string_lossy()).unwrap_or_default(); 
+                    if parser.parse(&stem).is_none() {
+                        return Err(invalid_name(path, "not a recognized TV or movie release name")); 
+                    }
+
+                    self.render_template(template, path, None)?
+                }
+                ReplaceMode::UrlDecode => url_decode(file_name).map_err(|err| invalid_name(path, &err))?, 
+                ReplaceMode::UrlEncode => utf8_percent_encode(file_name, URL_ENCODE_SET).to_string(), 
+                ReplaceMode::Tidy(tidy) => tidy.apply(file_name), 
+                ReplaceMode::Positional { edit, whole_name } => edit.apply(file_name, *whole_name), 
+                ReplaceMode::Dates(formatter) => match formatter.apply(file_name) {
+                    Ok(Some(name)) => name, 
+                    Ok(None) => return Ok(None), //no date in the name
+                    Err(err) => return Err(invalid_name(path, &err)), 
+                }, 
+                ReplaceMode::Pipeline(steps) => {
+                    //each step works on the name produced by the previous one
+                    let mut name = file_name.to_string(); 
+                    for step in steps {
+                        match self.replace_name(step, path, &name)? {
+                            Some(target_name) => name = target_name, 
+                            None => return Ok(None), 
+                        }
+                    }
+
+                    name
+                }
         }; 
 
-        match parent {
-            None => PathBuf::from(target_name), 
-            Some(path) => path.join(Path::new(&target_name))
+        if target_name.is_empty() {
+            return Err(invalid_name(path, "empty name")); 
         }
+
+        Ok(Some(target_name))
+    }
+
+
+    //render a template for the path, counting it as a renamed path
+    fn render_template(&self, template: &Template, path: &Path, captures: Option<&Captures>) -> Result<String> {
+        let index = self.counter.get(); 
+        self.counter.set(index + 1); 
+
+        let root = get_root(&self.config.run_mode, path); 
+        let context = Context { path, root: &root, captures, index }; 
+        template.render(&context).map_err(|err| invalid_name(path, &err))
+    }
+
+
+    //report which chars were transliterated and how
+    fn print_transliterated(&self) {
+        let transliterated = self.transliterated.borrow(); 
+        if transliterated.is_empty() {
+            return; 
+        }
+
+        let colors = &self.config.printer.colors; 
+        let replacements: Vec<String> = transliterated
+            .iter()
+            .map(|(c, replacement)| format!("{} -> '{}'", c, replacement))
+            .collect(); 
+
+        self.config.printer.print(&format!(
+            "{} Transliterated characters: {}", 
+            colors.info.paint("Info: "), 
+            replacements.join(", ")
+        )); 
     }
 
 
@@ -127,6 +308,16 @@ impl Renamer {
                 }
             }
 
+            //templates may move paths into new directories
+            if let Some(parent) = operation.target.parent() {
+                if !parent.as_os_str().is_empty() && fs::create_dir_all(parent).is_err() {
+                    return Err(Error {
+                        kind: ErrorKind::CreateFile, 
+                        value: Some(parent.to_string_lossy().to_string()), 
+                    }); 
+                }
+            }
+
             //rename paths in the filesystem
             if let Err(err) = fs::rename(&operation.source, &operation.target) {
                 return Err(Error {
@@ -153,36 +344,95 @@ impl Renamer {
         //Rename paths in the filesystem
     }
 
-    fn get_rename_map(&self, paths: &[PathBuf]) -> Result<RenameMap> {
+    //renaming a root moves every other planned path under it, so it is only renamed on its own
+    fn check_roots(&self, rename_map: &RenameMap) -> Result<()> {
+        let roots = match self.config.run_mode {
+            RunMode::Recursive { ref roots, .. } => roots, 
+            _ => return Ok(()), 
+        }; 
+
+        for root in roots.iter().map(|root| Path::new(&root.path)) {
+            let renamed = rename_map.values().any(|source| source == root); 
+            if renamed && rename_map.values().any(|source| source != root && source.starts_with(root)) {
+                return Err(Error {
+                    kind: ErrorKind::RenameRoot, 
+                    value: Some(root.to_string_lossy().to_string()), 
+                }); 
+            }
+        }
+
+        Ok(())
+    }
+
+
+    /** rename map of the paths, and the (link, target) symlinks whose target is renamed along */
+    fn get_rename_map(&self, paths: &[PathBuf]) -> Result<(RenameMap, Vec<(PathBuf, PathBuf)>)> {
         let printer = &self.config.printer; 
         let colors = &printer.colors; 
 
         let mut rename_map = RenameMap::new(); 
         let mut error_string = String::new(); 
 
+        //symlinks whose target is renamed, as (link, target)
+        let mut links = Vec::new(); 
+        let mut sources = Vec::new(); 
         for path in paths {
-            let target = self.replace_match(path); 
+            let is_link = path.symlink_metadata().map(|m| m.file_type().is_symlink()).unwrap_or(false); 
+            if !is_link || self.config.symlink_mode == SymlinkMode::Link {
+                sources.push(path.clone()); 
+                continue; 
+            }
+
+            if self.config.symlink_mode == SymlinkMode::Both {
+                sources.push(path.clone()); 
+            }
+            match link_target(path) {
+                Some(target) => {
+                    links.push((path.clone(), target.clone())); 
+                    sources.push(target); 
+                }
+                None => printer.print_warning(&invalid_name(path, "the link target cannot be renamed")), 
+            }
+        }
+
+        //a target reached through its link and listed on its own is renamed once
+        let mut listed = HashSet::new(); 
+        sources.retain(|path| listed.insert(absolute_path(path).unwrap_or_else(|_| path.clone()))); 
+
+        for path in &sources {
+            //report paths that cannot be renamed and leave them untouched
+            let target = match self.replace_match(path) {
+                Ok(target) => target, 
+                Err(err) => {
+                    printer.print_warning(&err); 
+                    continue; 
+                }
+            }; 
 
             if target != *path {
-                if let Some(old_path) = rename_map.insert(target.clone(), path.clone()) {
-                    //target cannot be duplicated be any reason
-                    error_string.push_str(
-                        &colors.
-                        error.paint(format!(
-                            "\n{0}->{2}\n{1}->{2}\n",
-                            old_path.display(), 
-                            path.display(), 
-                            target.display()
-                        ))
-                        .to_string()
-                    ); 
+                //a target reached through its link and listed on its own is renamed once
+                match rename_map.insert(target.clone(), path.clone()) {
+                    Some(old_path) if old_path != *path => {
+                        //target cannot be duplicated be any reason
+                        error_string.push_str(
+                            &colors.
+                            error.paint(format!(
+                                "\n{0}->{2}\n{1}->{2}\n",
+                                old_path.display(), 
+                                path.display(), 
+                                target.display()
+                            ))
+                            .to_string()
+                        ); 
+                    }
+                    _ => {}
                 }
             }
             
         }
 
         if error_string.is_empty() {
-            Ok(rename_map)
+            Ok((rename_map, links))
         
         }else {
             Err(Error{
@@ -197,7 +447,459 @@ impl Renamer {
 
 
 
+/** separator and word boundary cleanup, keeps the extension and the leading dot of hidden files as is */
+#[derive(Debug, Default)]
+pub struct Tidy {
+    pub separator: Option<char>, 
+    pub split_camel: bool, 
+}
+
+
+impl Tidy {
+    pub fn apply(&self, name: &str) -> String {
+        //".bashrc" is a hidden file, not an empty name with an extension
+        let (dot, name_without_dot) = match name.strip_prefix('.') {
+            Some(rest) => (".", rest), 
+            None => ("", name), 
+        }; 
+        let (stem, extension) = split_extension(name_without_dot); 
+        let stem = if self.split_camel { split_camel_case(stem) } else { stem.to_string() }; 
+
+        let mut tidy = String::from(dot); 
+        let mut separators = String::new(); 
+
+        for c in stem.chars() {
+            if is_separator(c) {
+                separators.push(c); 
+                continue; 
+            }
+
+            //separators at the start are trimmed
+            if tidy.len() > dot.len() && !separators.is_empty() {
+                tidy.push_str(&self.collapse(&separators)); 
+            }
+
+            separators.clear(); 
+            tidy.push(c); 
+        }
+
+        if tidy.len() == dot.len() {
+            return name.to_string(); 
+        }
+
+        match extension {
+            Some(extension) => format!("{}.{}", tidy, extension), 
+            None => tidy, 
+        }
+    }
+
+
+    //a run like " - - " becomes " - ", "__" becomes "_" and "  " becomes " "
+    fn collapse(&self, separators: &str) -> String {
+        if let Some(separator) = self.separator {
+            return separator.to_string(); 
+        }
+
+        let spaced = separators.chars().any(char::is_whitespace); 
+        match separators.chars().find(|c| !c.is_whitespace()) {
+            Some(mark) if spaced => format!(" {} ", mark), 
+            Some(mark) => mark.to_string(), 
+            None => String::from(" "), 
+        }
+    }
+}
+
+
+/** Bulk Rename Utility style edits, positions are counted in chars */
+#[derive(Debug, PartialEq)]
+pub enum Positional {
+    Insert(usize, String), //after the first N chars
+    Remove(usize, usize), //inclusive range counted from 1
+    Prepend(String), 
+    Append(String), 
+    Strip(usize), 
+}
+
+
+impl Positional {
+    /** edit the name without its extension, or the whole name */
+    pub fn apply(&self, name: &str, whole_name: bool) -> String {
+        let (stem, extension) = if whole_name { (name, None) } else { split_extension(name) }; 
+        let chars: Vec<char> = stem.chars().collect(); 
+
+        let edited: String = match self {
+            Positional::Insert(position, text) => {
+                let position = (*position).min(chars.len()); 
+                let (head, tail) = chars.split_at(position); 
+                format!("{}{}{}", head.iter().collect::<String>(), text, tail.iter().collect::<String>())
+            }
+            Positional::Remove(from, to) => chars
+                .iter()
+                .enumerate()
+                .filter(|(index, _)| index + 1 < *from || index + 1 > *to)
+                .map(|(_, c)| c)
+                .collect(), 
+            Positional::Prepend(text) => format!("{}{}", text, stem), 
+            Positional::Append(text) => format!("{}{}", stem, text), 
+            Positional::Strip(count) => chars.iter().skip(*count).collect(), 
+        }; 
+
+        //nothing left but the extension, reported as an empty name
+        if edited.is_empty() {
+            return edited; 
+        }
+
+        match extension {
+            Some(extension) => format!("{}.{}", edited, extension), 
+            None => edited, 
+        }
+    }
+}
+
+
+fn is_separator(c: char) -> bool {
+    c.is_whitespace() || c == '_' || c == '-' || c == '.'
+}
+
+
+/** 
+ * only a suffix of 1 to 5 letters or digits counts as an extension, so "v1.2 final" or
+ * "notes.markdown" are edited as a whole, a name starting with its only dot has none
+ */
+fn split_extension(name: &str) -> (&str, Option<&str>) {
+    match name.rfind('.') {
+        Some(index) if index > 0 => {
+            let extension = &name[index + 1..]; 
+            let length = extension.chars().count(); 
+            if (1..=5).contains(&length) && extension.chars().all(char::is_alphanumeric) {
+                (&name[..index], Some(extension))
+            } else {
+                (name, None)
+            }
+        }
+        _ => (name, None), 
+    }
+}
+
+
+//"myHTMLFile" -> "my HTML File"
+fn split_camel_case(name: &str) -> String {
+    let chars: Vec<char> = name.chars().collect(); 
+    let mut split = String::new(); 
+
+    for (index, c) in chars.iter().enumerate() {
+        if index > 0 && c.is_uppercase() {
+            let previous = chars[index - 1]; 
+            let next_lower = chars.get(index + 1).map(|n| n.is_lowercase()).unwrap_or(false); 
+
+            if previous.is_lowercase() || previous.is_numeric() || (previous.is_uppercase() && next_lower) {
+                split.push(' '); 
+            }
+        }
+
+        split.push(*c); 
+    }
+
+    split
+}
+
+
+
+/** strict percent-decoding, refuses names that would be unsafe as a file name */
+fn url_decode(name: &str) -> std::result::Result<String, String> {
+    let bytes: Vec<u8> = percent_decode_str(name).collect(); 
+
+    if bytes.contains(&b'/') {
+        return Err(String::from("decoded name contains '/'")); 
+    }
+
+    if bytes.contains(&0) {
+        return Err(String::from("decoded name contains NUL")); 
+    }
+
+    match String::from_utf8(bytes) {
+        Ok(decoded) => Ok(decoded), 
+        Err(_) => Err(String::from("decoded name is not valid UTF-8")), 
+    }
+}
+
+
+
+fn invalid_name(path: &Path, reason: &str) -> Error {
+    Error {
+        kind: ErrorKind::InvalidName,
+        value: Some(format!("{}: {}", path.display(), reason)),
+    }
+}
+
+
+
+//the path a symlink points to, seen from the link directory, if it exists and has a name
+/** 
+ * relinks of the symlinks whose target or own directory moves, relative links are
+ * rebuilt from the final directory of the link to the final path of the target
+ */
+fn get_relinks(rename_map: &RenameMap, links: &[(PathBuf, PathBuf)]) -> Result<Relinks> {
+    let final_paths = solver::FinalPaths::new(rename_map)?; 
+    let mut relinks = Relinks::new(); 
+
+    for (link, target) in links {
+        let old_contents = match fs::read_link(link) {
+            Ok(contents) => contents, 
+            Err(_) => continue, 
+        }; 
+
+        let (final_link, final_target) = (final_paths.get(link)?, final_paths.get(target)?); 
+        let new_contents = match final_link.parent() {
+            Some(parent) if old_contents.is_relative() => relative_path(&final_target, parent), 
+            _ => final_target.clone(), 
+        }; 
+
+        //"./a.txt" and "a.txt" are the same link, it is only rewritten when something it relies on moves
+        let link_dir_moved = final_link.parent() != absolute_path(link).ok().as_deref().and_then(Path::parent); 
+        let target_moved = absolute_path(target).ok() != Some(final_target); 
+        if (link_dir_moved || target_moved) && new_contents != old_contents {
+            relinks.push(Relink { source: link.clone(), target: final_link, old_contents, new_contents }); 
+        }
+    }
+
+    Ok(relinks)
+}
+
+
+fn link_target(link: &Path) -> Option<PathBuf> {
+    let contents = fs::read_link(link).ok()?;
+    contents.file_name()?;
+
+    let target = match link.parent() {
+        Some(parent) => normalize_path(&parent.join(&contents)),
+        None => contents,
+    };
+    target.symlink_metadata().ok()?;
+
+    Some(target)
+}
+
+
+
 #[cfg(test)]
 mod test {
+    use super::*;
+
+
+    #[test]
+    fn tidy_names() {
+        let tidy = Tidy::default(); 
+        assert_eq!(tidy.apply("My  File__name - - final.txt"), "My File_name - final.txt"); 
+        assert_eq!(tidy.apply("__draft--v2_.tar"), "draft-v2.tar"); 
+        assert_eq!(tidy.apply("..."), "..."); 
+        assert_eq!(tidy.apply("my__notes.markdown"), "my_notes.markdown"); 
+
+        let tidy = Tidy { separator: Some('_'), split_camel: true }; 
+        assert_eq!(tidy.apply("myHTMLFile - final copy.txt"), "my_HTML_File_final_copy.txt"); 
+        assert_eq!(tidy.apply("Report  2024 . final"), "Report_2024_final"); 
+    }
+
+
+    #[test]
+    fn tidy_hidden_files() {
+        let tidy = Tidy::default(); 
+        assert_eq!(tidy.apply(".bashrc"), ".bashrc"); 
+        assert_eq!(tidy.apply(".my__config.json"), ".my_config.json"); 
+        assert_eq!(tidy.apply(". _hidden  file"), ".hidden file"); 
+        assert_eq!(tidy.apply(".git"), ".git"); 
+
+        let tidy = Tidy { separator: Some('-'), split_camel: true }; 
+        assert_eq!(tidy.apply(".myConfig rc"), ".my-Config-rc"); 
+    }
+
+
+    #[test]
+    fn url_decode_names() {
+        assert_eq!(url_decode("My%20Report%281%29.pdf").unwrap(), "My Report(1).pdf"); 
+        assert_eq!(url_decode("100%.txt").unwrap(), "100%.txt"); 
+        assert!(url_decode("a%2Fb.txt").is_err()); 
+        assert!(url_decode("a%00b.txt").is_err()); 
+        assert!(url_decode("a%FFb.txt").is_err()); 
+    }
+
+
+    #[test]
+    fn url_encode_names() {
+        let encoded = utf8_percent_encode("My Report (1)_ü~.pdf", URL_ENCODE_SET).to_string(); 
+        assert_eq!(encoded, "My%20Report%20%281%29_%C3%BC~.pdf"); 
+        assert_eq!(url_decode(&encoded).unwrap(), "My Report (1)_ü~.pdf"); 
+    }
+
+
+    #[test]
+    fn positional_edits() {
+        assert_eq!(Positional::Insert(3, String::from("_")).apply("Übung.txt", false), "Übu_ng.txt"); 
+        assert_eq!(Positional::Insert(20, String::from("!")).apply("name.txt", false), "name!.txt"); 
+        assert_eq!(Positional::Remove(3, 7).apply("abcdefghij.txt", false), "abhij.txt"); 
+        assert_eq!(Positional::Prepend(String::from("2024 ")).apply("notes.md", false), "2024 notes.md"); 
+        assert_eq!(Positional::Append(String::from("_v2")).apply("notes.md", false), "notes_v2.md"); 
+        assert_eq!(Positional::Strip(4).apply("IMG_éclair.jpg", false), "éclair.jpg"); 
+
+        assert_eq!(Positional::Append(String::from(".bak")).apply("notes.md", true), "notes.md.bak"); 
+        assert_eq!(Positional::Strip(2).apply("ab.txt", false), ""); 
+    }
 
-}
\ No newline at end of file
+
+    #[cfg(unix)]
+    #[test]
+    fn symlink_modes() {
+        use crate::config::SortOrder;
+        use crate::output::Printer;
+        use regex::Regex;
+
+        let tempdir = tempfile::tempdir().unwrap();
+        let root = tempdir.path();
+        fs::create_dir(root.join("data")).unwrap();
+        fs::write(root.join("data/report.txt"), "").unwrap();
+        create_symlink(Path::new("data/report.txt"), &root.join("report.txt")).unwrap();
+
+        let rename = |symlink_mode| {
+            let config = Config {
+                force: true,
+                backup: false,
+                dirs: false,
+                dump: false,
+                symlink_mode,
+                sort: SortOrder::Natural,
+                reverse: false,
+                run_mode: RunMode::Simple(vec![root.join("report.txt").to_string_lossy().to_string()]),
+                replace_mode: ReplaceMode::RegExp { expression: Regex::new("report").unwrap(), replacement: String::from("summary"), limit: 1 },
+                printer: Printer::silent(),
+            };
+            let renamer = Renamer::new(&Arc::new(config)).unwrap();
+            renamer.batch_rename(renamer.process().unwrap()).unwrap();
+        };
+
+        //the link keeps its name and follows its renamed target
+        rename(SymlinkMode::Target);
+        assert!(root.join("data/summary.txt").is_file());
+        assert_eq!(fs::read_link(root.join("report.txt")).unwrap(), Path::new("data/summary.txt"));
+
+        fs::rename(root.join("data/summary.txt"), root.join("data/report.txt")).unwrap();
+        fs::remove_file(root.join("report.txt")).unwrap();
+        create_symlink(Path::new("data/report.txt"), &root.join("report.txt")).unwrap();
+
+        rename(SymlinkMode::Both);
+        assert_eq!(fs::read_link(root.join("summary.txt")).unwrap(), Path::new("data/summary.txt"));
+        assert!(root.join("summary.txt").is_file());
+    }
+
+
+    //dry run renaming "." to "x" in the names
+    fn dry_run(run_mode: RunMode) -> Renamer {
+        use crate::config::SortOrder;
+        use crate::output::Printer;
+        use regex::Regex;
+
+        let config = Config {
+            force: false,
+            backup: false,
+            dirs: true,
+            dump: false,
+            symlink_mode: SymlinkMode::Link,
+            sort: SortOrder::Natural,
+            reverse: false,
+            run_mode,
+            replace_mode: ReplaceMode::RegExp { expression: Regex::new(".").unwrap(), replacement: String::from("x"), limit: 1 },
+            printer: Printer::silent(),
+        };
+        Renamer::new(&Arc::new(config)).unwrap()
+    }
+
+
+    #[test]
+    fn unnamed_roots() {
+        use crate::config::WalkRoot;
+        use crate::filter::{PathFilter, Predicates};
+
+        //rx -r -D . x . lists the root itself, it has no name to rename
+        let renamer = dry_run(RunMode::Recursive {
+            roots: vec![WalkRoot { path: String::from("."), min_depth: 0, max_depth: Some(0) }],
+            hidden: false,
+            follow: false,
+            one_file_system: false,
+            threads: 1,
+            filter: PathFilter::default(),
+            predicates: Predicates::default(),
+            ignore: false,
+        });
+        assert!(renamer.process().unwrap().is_empty());
+    }
+
+
+    #[cfg(unix)]
+    #[test]
+    fn non_utf8_names() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = tempdir.path().join(OsStr::from_bytes(b"caf\xe9.txt"));
+        fs::write(&path, "").unwrap();
+
+        let renamer = dry_run(RunMode::Simple(vec![]));
+        assert!(matches!(renamer.replace_match(&path).unwrap_err().kind, ErrorKind::InvalidName));
+    }
+
+
+    #[cfg(unix)]
+    #[test]
+    fn relinks_follow_moved_directories() {
+        use crate::config::SortOrder;
+        use crate::output::Printer;
+        use regex::Regex;
+
+        let tempdir = tempfile::tempdir().unwrap();
+        let root = tempdir.path();
+        for dir in &["data", "links"] {
+            fs::create_dir(root.join(dir)).unwrap();
+        }
+        fs::write(root.join("data/report.txt"), "report").unwrap();
+        create_symlink(Path::new("data/report.txt"), &root.join("report.txt")).unwrap();
+        create_symlink(Path::new("../data/report.txt"), &root.join("links/report.txt")).unwrap();
+
+        let renamer = |run_mode| {
+            let config = Config {
+                force: true,
+                backup: false,
+                dirs: true,
+                dump: false,
+                symlink_mode: SymlinkMode::Both,
+                sort: SortOrder::Natural,
+                reverse: false,
+                run_mode,
+                replace_mode: ReplaceMode::RegExp { expression: Regex::new("data|report").unwrap(), replacement: String::from("new"), limit: 1 },
+                printer: Printer::silent(),
+            };
+            Renamer::new(&Arc::new(config)).unwrap()
+        };
+
+        //data is renamed along with the file, the links are rebuilt from their own directory
+        let paths = ["data", "report.txt", "links/report.txt"].iter().map(|p| root.join(p).to_string_lossy().to_string()).collect();
+        let forward = renamer(RunMode::Simple(paths));
+        let operations = forward.process().unwrap();
+        forward.batch_rename(operations.clone()).unwrap();
+
+        assert_eq!(fs::read_link(root.join("new.txt")).unwrap(), Path::new("new/new.txt"));
+        assert_eq!(fs::read_link(root.join("links/new.txt")).unwrap(), Path::new("../new/new.txt"));
+        assert_eq!(fs::read_to_string(root.join("links/new.txt")).unwrap(), "report");
+        assert!(!root.join("data").exists());
+
+        //undo puts the files back and the links on their old contents
+        let dump = root.join("rx-dump.json");
+        dumpfile::write_dump(&dump, &operations, &forward.relinks.borrow()).unwrap();
+        let undo = renamer(RunMode::FromFile { path: dump.to_string_lossy().to_string(), undo: true });
+        undo.batch_rename(undo.process().unwrap()).unwrap();
+
+        assert_eq!(fs::read_link(root.join("report.txt")).unwrap(), Path::new("data/report.txt"));
+        assert_eq!(fs::read_link(root.join("links/report.txt")).unwrap(), Path::new("../data/report.txt"));
+        assert_eq!(fs::read_to_string(root.join("links/report.txt")).unwrap(), "report");
+        assert!(!root.join("new").exists());
+    }
+}
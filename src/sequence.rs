@@ -0,0 +1,294 @@
+//frame sequences renumbered as a unit, e.g. shot_0987.exr ... shot_1240.exr
+
+use crate::error::*;
+use crate::renamer::RenameMap;
+use regex::Regex;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+
+
+lazy_static! {
+    //prefix, frame number and what follows it in the stem
+    static ref FRAME: Regex = Regex::new(r"^(.*?)(\d+)(\D*)$").unwrap();
+}
+
+
+
+/** how frames are spaced once renumbered */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Spacing {
+    Keep,
+    Compact(u64),
+    Scale(u64, u64),
+}
+
+
+
+#[derive(Debug)]
+pub struct Renumber {
+    pub start: Option<u64>,
+    pub offset: i64,
+    pub spacing: Spacing,
+    pub padding: Option<usize>,
+}
+
+
+
+/** two or more frames sharing a directory, prefix, padding and suffix */
+#[derive(Debug, PartialEq)]
+struct Sequence {
+    parent: PathBuf,
+    prefix: String,
+    suffix: String,
+    width: usize,
+    frames: Vec<(u64, PathBuf)>,
+}
+
+
+
+/** renumber every sequence found in the paths, files outside a sequence are left untouched */
+pub fn rename_map(paths: &[PathBuf], renumber: &Renumber) -> Result<RenameMap> {
+    let mut rename_map = RenameMap::new();
+
+    for sequence in detect_sequences(paths) {
+        let frames: Vec<u64> = sequence.frames.iter().map(|(frame, _)| *frame).collect();
+        let width = renumber.padding.unwrap_or(sequence.width);
+
+        let new_frames = renumber.apply(&frames).map_err(|err| Error {
+            kind: ErrorKind::InvalidName,
+            value: Some(format!("{}: {}", sequence.pattern().display(), err)),
+        })?;
+
+        for ((_, source), frame) in sequence.frames.iter().zip(new_frames) {
+            let name = format!("{}{:0width$}{}", sequence.prefix, frame, sequence.suffix, width = width);
+            let target = sequence.parent.join(name);
+
+            if target == *source {
+                continue;
+            }
+
+            if let Some(other) = rename_map.insert(target.clone(), source.clone()) {
+                return Err(Error {
+                    kind: ErrorKind::SameFilename,
+                    value: Some(format!("\n{0}->{2}\n{1}->{2}\n", other.display(), source.display(), target.display())),
+                });
+            }
+        }
+    }
+
+    Ok(rename_map)
+}
+
+
+
+impl Renumber {
+    //new frame numbers, in the order of the given frames
+    fn apply(&self, frames: &[u64]) -> std::result::Result<Vec<u64>, String> {
+        let first = match frames.first() {
+            Some(first) => *first,
+            None => return Ok(Vec::new()),
+        };
+
+        let base = match self.start {
+            Some(start) => start as i128,
+            None => first as i128 + self.offset as i128,
+        };
+
+        let mut new_frames = Vec::new();
+        for (index, frame) in frames.iter().enumerate() {
+            let distance = match self.spacing {
+                Spacing::Keep => (frame - first) as i128,
+                Spacing::Compact(step) => index as i128 * step as i128,
+                Spacing::Scale(numerator, denominator) => {
+                    let scaled = (frame - first) as i128 * numerator as i128;
+                    if scaled % denominator as i128 != 0 {
+                        return Err(format!("frame {} does not scale to a whole frame number", frame));
+                    }
+
+                    scaled / denominator as i128
+                }
+            };
+
+            let new_frame = base + distance;
+            if new_frame < 0 || new_frame > u64::MAX as i128 {
+                return Err(format!("frame {} would be renumbered to {}", frame, new_frame));
+            }
+
+            new_frames.push(new_frame as u64);
+        }
+
+        Ok(new_frames)
+    }
+}
+
+
+
+impl Sequence {
+    //"shot_####.exr" like notation used in messages
+    fn pattern(&self) -> PathBuf {
+        let hashes = "#".repeat(self.width.max(1));
+        self.parent.join(format!("{}{}{}", self.prefix, hashes, self.suffix))
+    }
+}
+
+
+
+//group files by directory, prefix and suffix, then split the groups by padding,
+//a lone numbered file like "notes_v2.txt" is not a sequence
+fn detect_sequences(paths: &[PathBuf]) -> Vec<Sequence> {
+    let mut groups: BTreeMap<(PathBuf, String, String), Vec<(String, PathBuf)>> = BTreeMap::new();
+
+    for path in paths.iter().filter(|p| !p.is_dir()) {
+        if let Some((prefix, digits, suffix)) = split_frame(path) {
+            let parent = path.parent().map(Path::to_path_buf).unwrap_or_default();
+            groups.entry((parent, prefix, suffix)).or_default().push((digits, path.clone()));
+        }
+    }
+
+    let mut sequences = Vec::new();
+    for ((parent, prefix, suffix), members) in groups {
+        //"0987" is padded to 4, "1240" fits that padding and "12" does not
+        let padded: Vec<usize> = members.iter().filter(|(d, _)| is_padded(d)).map(|(d, _)| d.len()).collect();
+
+        let mut by_width: BTreeMap<usize, Vec<(u64, PathBuf)>> = BTreeMap::new();
+        for (digits, path) in members {
+            let width = if is_padded(&digits) {
+                digits.len()
+            } else {
+                padded.iter().filter(|w| **w <= digits.len()).max().cloned().unwrap_or(0)
+            };
+
+            if let Ok(frame) = digits.parse::<u64>() {
+                by_width.entry(width).or_default().push((frame, path));
+            }
+        }
+
+        for (width, mut frames) in by_width.into_iter().filter(|(_, frames)| frames.len() > 1) {
+            frames.sort();
+            sequences.push(Sequence {
+                parent: parent.clone(),
+                prefix: prefix.clone(),
+                suffix: suffix.clone(),
+                width,
+                frames,
+            });
+        }
+    }
+
+    sequences
+}
+
+
+fn is_padded(digits: &str) -> bool {
+    digits.len() > 1 && digits.starts_with('0')
+}
+
+
+//the frame number is the last run of digits before the extension
+fn split_frame(path: &Path) -> Option<(String, String, String)> {
+    let stem = path.file_stem()?.to_str()?;
+    let extension = match path.extension() {
+        Some(extension) => format!(".{}", extension.to_str()?),
+        None => String::new(),
+    };
+
+    let captures = FRAME.captures(stem)?;
+
+    Some((
+        captures[1].to_string(),
+        captures[2].to_string(),
+        format!("{}{}", &captures[3], extension),
+    ))
+}
+
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::solver;
+    use std::fs;
+
+
+    fn renumber(start: Option<u64>, offset: i64, spacing: Spacing) -> Renumber {
+        Renumber { start, offset, spacing, padding: None }
+    }
+
+
+    #[test]
+    fn detect_padding() {
+        let paths: Vec<PathBuf> = ["shot_0987.exr", "shot_1240.exr", "shot_12.exr", "shot_13.exr", "clip_0001.mp4", "clip_0002.mp4", "notes.txt"]
+            .iter()
+            .map(PathBuf::from)
+            .collect();
+
+        let sequences = detect_sequences(&paths);
+        let summary: Vec<(String, usize, Vec<u64>)> = sequences
+            .iter()
+            .map(|s| (s.pattern().display().to_string(), s.width, s.frames.iter().map(|f| f.0).collect()))
+            .collect();
+
+        assert_eq!(summary, vec![
+            (String::from("clip_####.mp4"), 4, vec![1, 2]),
+            (String::from("shot_#.exr"), 0, vec![12, 13]),
+            (String::from("shot_####.exr"), 4, vec![987, 1240]),
+        ]);
+    }
+
+
+    #[test]
+    fn single_numbered_files() {
+        let paths: Vec<PathBuf> = ["notes_v2.txt", "report2024.pdf", "shot_0001.exr", "shot_2.exr", "shot_3.exr"]
+            .iter()
+            .map(PathBuf::from)
+            .collect();
+
+        let sequences = detect_sequences(&paths);
+        assert_eq!(sequences.len(), 1);
+        assert_eq!(sequences[0].pattern(), PathBuf::from("shot_#.exr"));
+
+        let rename_map = rename_map(&paths, &renumber(Some(10), 0, Spacing::Keep)).unwrap();
+        let mut sources: Vec<&PathBuf> = rename_map.values().collect();
+        sources.sort();
+        assert_eq!(sources, vec![Path::new("shot_2.exr"), Path::new("shot_3.exr")]);
+    }
+
+
+    #[test]
+    fn renumber_frames() {
+        let frames = [987, 988, 990, 1240];
+
+        assert_eq!(renumber(Some(1001), 0, Spacing::Keep).apply(&frames).unwrap(), vec![1001, 1002, 1004, 1254]);
+        assert_eq!(renumber(None, 1, Spacing::Keep).apply(&frames).unwrap(), vec![988, 989, 991, 1241]);
+        assert_eq!(renumber(Some(1), 0, Spacing::Compact(1)).apply(&frames).unwrap(), vec![1, 2, 3, 4]);
+        assert_eq!(renumber(None, 0, Spacing::Compact(10)).apply(&frames).unwrap(), vec![987, 997, 1007, 1017]);
+        assert_eq!(renumber(Some(0), 0, Spacing::Scale(1, 2)).apply(&[10, 12, 16]).unwrap(), vec![0, 1, 3]);
+
+        assert!(renumber(None, -1000, Spacing::Keep).apply(&frames).is_err());
+        assert!(renumber(None, 0, Spacing::Scale(1, 2)).apply(&[1, 2]).is_err());
+    }
+
+
+    #[test]
+    fn offset_chains() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths: Vec<PathBuf> = (1..=5).map(|i| dir.path().join(format!("shot_{:04}.exr", i))).collect();
+        for path in &paths {
+            fs::write(path, path.to_str().unwrap()).unwrap();
+        }
+
+        //every target but the last one is the source of another rename
+        let rename_map = rename_map(&paths, &renumber(None, 1, Spacing::Keep)).unwrap();
+        for operation in solver::solve_rename_order(&rename_map).unwrap() {
+            assert!(!operation.target.exists(), "{} overwritten", operation.target.display());
+            fs::rename(&operation.source, &operation.target).unwrap();
+        }
+
+        for i in 2..=6 {
+            let path = dir.path().join(format!("shot_{:04}.exr", i));
+            let previous = dir.path().join(format!("shot_{:04}.exr", i - 1));
+            assert_eq!(fs::read_to_string(path).unwrap(), previous.to_str().unwrap());
+        }
+    }
+}
@@ -1,11 +1,11 @@
 
 
-use crate::dumpfile::{Operations, Operation};
+use crate::dumpfile::{Operations, Operation, Relink, Relinks};
 use crate::error::*;
-use crate::fileutils::{ PathList, is_same_file};
+use crate::fileutils::{ PathList, absolute_path, is_same_file};
 use crate::renamer::RenameMap;
-use path_abs::PathAbs;
-use std::path::PathBuf;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
 
 
 #[allow(dead_code)]
@@ -14,7 +14,7 @@ use std::path::PathBuf;
 pub fn solve_rename_order(rename_map: &RenameMap) -> Result<Operations> {
     //solve the renaming order of the files
     let mut level_list: Vec<usize> = rename_map
-        .values()
+        .keys()
         .map(|x| x.components()
         .count()).collect(); 
         
@@ -56,19 +56,180 @@ pub fn solve_rename_order(rename_map: &RenameMap) -> Result<Operations> {
         }
     }
 
+    let (operations, final_paths) = rewrite_moved_paths(rename_map, &rename_order)?; 
+    check_final_tree(rename_map, &rename_order, &final_paths)?; 
+
+    Ok(operations)
+} 
+
+
+
+
+/** 
+ * rename map paths are the ones before any rename, once a directory is renamed the
+ * paths of the operations under it are rewritten, the final path of each one is returned too
+ */
+fn rewrite_moved_paths(rename_map: &RenameMap, rename_order: &[PathBuf]) -> Result<(Operations, PathList)> {
     let mut operations = Operations::new(); 
+    //directory renames done so far and the count of them when each operation runs
+    let mut moves = Moves::default(); 
+    let mut moves_before = Vec::new(); 
 
-    
     for target in rename_order {
-        operations.push(Operation {
-            source: rename_map[&target].clone(), 
-            target
-        })
+        let source = &rename_map[target]; 
+        let operation = Operation {
+            source: moves.moved_path(source, 0), 
+            target: moves.moved_path(target, 0), 
+        }; 
+
+        if source.symlink_metadata().map(|m| m.is_dir()).unwrap_or(false) {
+            if is_strict_descendant(&operation.target, &operation.source) {
+                return Err(Error {
+                    kind: ErrorKind::InvalidMapping, 
+                    value: Some(format!("{} -> {} moves a directory into itself", source.display(), target.display())), 
+                }); 
+            }
+            moves.push(operation.source.clone(), operation.target.clone()); 
+        }
+
+        moves_before.push(moves.len()); 
+        operations.push(operation); 
     }
 
+    //later renames of the directories above still move it
+    let final_paths = operations.iter()
+        .zip(moves_before)
+        .map(|(operation, before)| moves.moved_path(&operation.target, before))
+        .collect(); 
 
-    Ok(operations)
-} 
+    Ok((operations, final_paths))
+}
+
+
+
+/** directory renames in the order they run, indexed by the directory they move */
+#[derive(Default)]
+struct Moves {
+    count: usize, 
+    //source directory -> (run index, new path) of each time it is moved
+    by_source: BTreeMap<PathBuf, Vec<(usize, PathBuf)>>, 
+}
+
+
+impl Moves {
+    fn push(&mut self, from: PathBuf, to: PathBuf) {
+        self.by_source.entry(from).or_default().push((self.count, to)); 
+        self.count += 1; 
+    }
+
+
+    fn len(&self) -> usize {
+        self.count
+    }
+
+
+    //path once moved by the renames from index first on, only the ancestors of the path are looked up
+    fn moved_path(&self, path: &Path, first: usize) -> PathBuf {
+        let mut path = path.to_path_buf(); 
+        let mut next = first; 
+
+        loop {
+            //the earliest rename still to run that moves one of the directories above
+            let earliest = path.ancestors()
+                .skip(1)
+                .filter_map(|ancestor| {
+                    let moves = self.by_source.get(ancestor)?; 
+                    let (index, to) = moves.get(moves.partition_point(|(index, _)| *index < next))?; 
+                    Some((*index, ancestor.components().count(), to))
+                })
+                .min_by_key(|(index, _, _)| *index); 
+
+            match earliest {
+                Some((index, depth, to)) => {
+                    path = to.join(path.components().skip(depth).collect::<PathBuf>()); 
+                    next = index + 1; 
+                }
+                None => return path, 
+            }
+        }
+    }
+}
+
+
+fn is_strict_descendant(path: &Path, ancestor: &Path) -> bool {
+    path != ancestor && path.starts_with(ancestor)
+}
+
+
+
+/** the final paths are unique and a renamed directory only holds what was already in it or moved into it */
+fn check_final_tree(rename_map: &RenameMap, rename_order: &[PathBuf], final_paths: &[PathBuf]) -> Result<()> {
+    let mut final_index: HashMap<&PathBuf, usize> = HashMap::new(); 
+    for (index, final_path) in final_paths.iter().enumerate() {
+        if let Some(other) = final_index.insert(final_path, index) {
+            return Err(Error {
+                kind: ErrorKind::SameFilename, 
+                value: Some(format!(
+                    "\n{0}->{2}\n{1}->{2}\n", 
+                    rename_map[&rename_order[other]].display(), 
+                    rename_map[&rename_order[index]].display(), 
+                    final_path.display()
+                )), 
+            }); 
+        }
+    }
+
+    let source_index: HashMap<&PathBuf, usize> = rename_order.iter()
+        .enumerate()
+        .map(|(index, target)| (&rename_map[target], index))
+        .collect(); 
+
+    for (index, final_path) in final_paths.iter().enumerate() {
+        for ancestor in final_path.ancestors().skip(1) {
+            let directory = match final_index.get(&ancestor.to_path_buf()) {
+                Some(directory) => *directory, 
+                None => continue, 
+            }; 
+
+            //a directory created for this path would be in the way of the renamed one
+            if !is_moved_into(index, directory, rename_order, &source_index) {
+                return Err(Error {
+                    kind: ErrorKind::InvalidMapping, 
+                    value: Some(format!(
+                        "{} -> {} ends up in {}, the new name of {}", 
+                        rename_map[&rename_order[index]].display(), 
+                        final_path.display(), 
+                        ancestor.display(), 
+                        rename_map[&rename_order[directory]].display()
+                    )), 
+                }); 
+            }
+        }
+    }
+
+    Ok(())
+}
+
+
+//whether the target of an operation lies in a renamed directory, directly or through other renamed directories
+fn is_moved_into(index: usize, directory: usize, rename_order: &[PathBuf], source_index: &HashMap<&PathBuf, usize>) -> bool {
+    let mut target = &rename_order[index]; 
+
+    //each step goes through another operation, more than all of them is a cycle
+    for _ in 0..=rename_order.len() {
+        let parent = target.ancestors()
+            .skip(1)
+            .find_map(|ancestor| source_index.get(&ancestor.to_path_buf())); 
+
+        match parent {
+            Some(parent) if *parent == directory => return true, 
+            Some(parent) => target = &rename_order[*parent], 
+            None => return false, 
+        }
+    }
+
+    false
+}
 
 
 
@@ -79,14 +240,69 @@ pub fn revert_operations(operations: &[Operation]) -> Result<Operations> {
     
 
     let inverse_operation = reverse_operations.into_iter().map(|Operation { source, target} | Operation {
-        source, 
-        target
+        source: target, 
+        target: source
     }).collect(); 
 
     Ok(inverse_operation)
 }
 
 
+//links are pointed back at their old contents, from where the reverted operations leave them
+pub fn revert_relinks(relinks: &[Relink]) -> Relinks {
+    relinks.iter().rev().map(|relink| Relink {
+        source: relink.target.clone(), 
+        target: relink.source.clone(), 
+        old_contents: relink.new_contents.clone(), 
+        new_contents: relink.old_contents.clone(), 
+    }).collect()
+}
+
+
+
+/** where any path ends up once a rename map is applied, renamed itself or moved along with a renamed directory */
+pub struct FinalPaths {
+    //absolute source -> absolute target, the parents of a target are the ones before any rename
+    targets: HashMap<PathBuf, PathBuf>, 
+}
+
+
+impl FinalPaths {
+    pub fn new(rename_map: &RenameMap) -> Result<FinalPaths> {
+        let targets = rename_map.iter()
+            .map(|(target, source)| Ok((absolute(source)?, absolute(target)?)))
+            .collect::<Result<HashMap<PathBuf, PathBuf>>>()?; 
+
+        Ok(FinalPaths { targets })
+    }
+
+
+    /** absolute final path */
+    pub fn get(&self, path: &Path) -> Result<PathBuf> {
+        let path = absolute(path)?; 
+        self.resolve(&path, self.targets.len()).ok_or_else(|| Error {
+            kind: ErrorKind::InvalidMapping, 
+            value: Some(format!("{} is moved into itself", path.display())), 
+        })
+    }
+
+
+    //each rename followed uses a step, needing more than all of them is a cycle
+    fn resolve(&self, path: &Path, steps: usize) -> Option<PathBuf> {
+        let (path, steps) = match self.targets.get(path) {
+            Some(target) => (target.as_path(), steps.checked_sub(1)?), 
+            None => (path, steps), 
+        }; 
+
+        match (path.parent(), path.file_name()) {
+            (Some(parent), Some(name)) => Some(self.resolve(parent, steps)?.join(name)), 
+            _ => Some(path.to_path_buf()), 
+        }
+    }
+}
+
+
+
 /** get existing target on user system */
 fn get_existing_targets(targets: &[PathBuf], rename_map: &RenameMap) -> Result<PathList> {
     let mut existing_target :PathList = Vec::new(); 
@@ -98,10 +314,10 @@ fn get_existing_targets(targets: &[PathBuf], rename_map: &RenameMap) -> Result<P
         
 
         if !rename_map.values().any(|x| x == target){
-            let source = rename_map.get(target).cloned().unwrap(); 
+            let source = source_of(rename_map, target)?; 
 
 
-            if is_same_file(&source, target) {
+            if is_same_file(source, target) {
                 continue; 
             }
             
@@ -126,14 +342,13 @@ fn sort_existing_target(rename_map: &RenameMap, existing_target: &mut PathList)
         let mut selected_index: Option<usize>= None; //selected index
 
         let sources:PathList = existing_target.iter()
-            .map(|x| rename_map.get(x).cloned().unwrap())
-            .map(|p| PathAbs::new(p).unwrap().to_path_buf())
-            .collect();
+            .map(|x| source_of(rename_map, x).and_then(|p| absolute(p)))
+            .collect::<Result<PathList>>()?;
 
 
         //select without conflict 
         for (index, target) in existing_target.iter().enumerate() {
-            let absolute_target = PathAbs::new(target).unwrap().to_path_buf(); 
+            let absolute_target = absolute(target)?; 
             if !sources.contains(&absolute_target) {
                 selected_index = Some(index); 
                 break;
@@ -143,7 +358,7 @@ fn sort_existing_target(rename_map: &RenameMap, existing_target: &mut PathList)
 
         //store result in ordered targets 
         match selected_index {
-            Some(index) => ordered_target.push(existing_target.swap_remove(index)),
+            Some(index) => ordered_target.push(existing_target.remove(index)),
             None =>  {
                 return Err(Error {
                     kind: ErrorKind::SolveOrder, 
@@ -154,4 +369,178 @@ fn sort_existing_target(rename_map: &RenameMap, existing_target: &mut PathList)
     }
 
     Ok(ordered_target)
-}
\ No newline at end of file
+}
+
+
+//every target the solver orders comes from the rename map
+fn source_of<'a>(rename_map: &'a RenameMap, target: &Path) -> Result<&'a PathBuf> {
+    rename_map.get(target).ok_or_else(|| Error {
+        kind: ErrorKind::SolveOrder, 
+        value: Some(format!("no source for {}", target.display())), 
+    })
+}
+
+
+fn absolute(path: &Path) -> Result<PathBuf> {
+    absolute_path(path).map_err(|err| Error {
+        kind: ErrorKind::SolveOrder, 
+        value: Some(format!("{}: {}", path.display(), err)), 
+    })
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::dumpfile;
+    use std::fs;
+
+
+    #[test]
+    fn nested_renames() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let root = tempdir.path();
+        for dir in &["Foo", "Baz"] {
+            fs::create_dir(root.join(dir)).unwrap();
+        }
+        for file in &["Foo/foo.txt", "Baz/in.txt", "x.txt"] {
+            fs::write(root.join(file), "").unwrap();
+        }
+
+        //Foo is renamed before the paths under it, they are rewritten
+        let rename_map: RenameMap = [
+            ("Archive/Bar", "Foo"),
+            ("Foo/bar.txt", "Foo/foo.txt"),
+            ("Foo/x.txt", "x.txt"),
+            ("Foo/Baz", "Baz"),
+            ("Baz/out.txt", "Baz/in.txt"),
+        ].iter().map(|(target, source)| (root.join(target), root.join(source))).collect();
+
+        for operation in solve_rename_order(&rename_map).unwrap() {
+            fs::create_dir_all(operation.target.parent().unwrap()).unwrap();
+            fs::rename(&operation.source, &operation.target).unwrap();
+        }
+
+        for file in &["Archive/Bar/bar.txt", "Archive/Bar/x.txt", "Archive/Bar/Baz/out.txt"] {
+            assert!(root.join(file).is_file(), "{} is missing", file);
+        }
+        assert!(!root.join("Foo").exists());
+    }
+
+
+    #[test]
+    fn levels_follow_target_depth() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let root = tempdir.path();
+        fs::create_dir_all(root.join("sub/dir")).unwrap();
+        for file in &["a.txt", "b.txt"] {
+            fs::write(root.join(file), "").unwrap();
+        }
+
+        //levels come from the targets, no source is as deep as sub/dir/a.txt
+        let rename_map: RenameMap = [
+            ("sub/dir/a.txt", "a.txt"),
+            ("c.txt", "b.txt"),
+        ].iter().map(|(target, source)| (root.join(target), root.join(source))).collect();
+
+        let operations = solve_rename_order(&rename_map).unwrap();
+        assert_eq!(operations.len(), 2);
+        assert_eq!(operations[0].target, root.join("sub/dir/a.txt"));
+        assert_eq!(operations[1].target, root.join("c.txt"));
+    }
+
+
+    #[test]
+    fn chained_moves() {
+        let mut moves = Moves::default();
+        moves.push(PathBuf::from("a"), PathBuf::from("b"));
+        moves.push(PathBuf::from("b/c"), PathBuf::from("d"));
+        moves.push(PathBuf::from("a"), PathBuf::from("e"));
+
+        assert_eq!(moves.moved_path(Path::new("a/c/f.txt"), 0), PathBuf::from("d/f.txt"));
+        assert_eq!(moves.moved_path(Path::new("a/x.txt"), 0), PathBuf::from("b/x.txt"));
+        assert_eq!(moves.moved_path(Path::new("a/x.txt"), 1), PathBuf::from("e/x.txt"));
+        assert_eq!(moves.moved_path(Path::new("b/c"), 0), PathBuf::from("b/c"));
+        assert_eq!(moves.moved_path(Path::new("ab/x.txt"), 0), PathBuf::from("ab/x.txt"));
+        assert_eq!(moves.len(), 3);
+    }
+
+
+    #[test]
+    fn final_paths() {
+        let rename_map: RenameMap = [
+            ("/r/Archive/Bar", "/r/Foo"),
+            ("/r/Foo/bar.txt", "/r/Foo/foo.txt"),
+            ("/r/Foo/Baz", "/r/Baz"),
+        ].iter().map(|(target, source)| (PathBuf::from(target), PathBuf::from(source))).collect();
+        let final_paths = FinalPaths::new(&rename_map).unwrap();
+
+        assert_eq!(final_paths.get(Path::new("/r/Foo/foo.txt")).unwrap(), PathBuf::from("/r/Archive/Bar/bar.txt"));
+        assert_eq!(final_paths.get(Path::new("/r/Foo/other.txt")).unwrap(), PathBuf::from("/r/Archive/Bar/other.txt"));
+        assert_eq!(final_paths.get(Path::new("/r/Baz/in.txt")).unwrap(), PathBuf::from("/r/Archive/Bar/Baz/in.txt"));
+        assert_eq!(final_paths.get(Path::new("/r/x/../y.txt")).unwrap(), PathBuf::from("/r/y.txt"));
+
+        let cycle: RenameMap = [("/r/b/x", "/r/a"), ("/r/a/y", "/r/b")].iter().map(|(target, source)| (PathBuf::from(target), PathBuf::from(source))).collect();
+        assert!(FinalPaths::new(&cycle).unwrap().get(Path::new("/r/a")).is_err());
+    }
+
+
+    #[test]
+    fn invalid_final_tree() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let root = tempdir.path();
+        fs::create_dir(root.join("Foo")).unwrap();
+        fs::write(root.join("y.txt"), "").unwrap();
+
+        let solve = |renames: &[(&str, &str)]| {
+            let rename_map: RenameMap = renames.iter().map(|(target, source)| (root.join(target), root.join(source))).collect();
+            solve_rename_order(&rename_map).map(|_| ())
+        };
+
+        //Bar would be created for y.txt before Foo is renamed to it
+        assert!(solve(&[("Bar", "Foo"), ("Bar/y.txt", "y.txt")]).is_err());
+        assert!(solve(&[("Foo/Foo", "Foo")]).is_err());
+        assert!(solve(&[("Bar", "Foo"), ("Foo/y.txt", "y.txt")]).is_ok());
+    }
+
+
+    #[test]
+    fn undo_dump() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let root = tempdir.path();
+        fs::create_dir(root.join("Foo")).unwrap();
+        for file in &["Foo/a.txt", "b.txt"] {
+            fs::write(root.join(file), *file).unwrap();
+        }
+
+        let apply = |operations: &Operations| for operation in operations {
+            fs::create_dir_all(operation.target.parent().unwrap()).unwrap();
+            fs::rename(&operation.source, &operation.target).unwrap();
+        };
+
+        let rename_map: RenameMap = [
+            ("Bar", "Foo"),
+            ("Foo/c.txt", "Foo/a.txt"),
+            ("Foo/b.txt", "b.txt"),
+        ].iter().map(|(target, source)| (root.join(target), root.join(source))).collect();
+        let operations = solve_rename_order(&rename_map).unwrap();
+        apply(&operations);
+
+        //undo reads the operations back from a dump, each one runs from its target to its source
+        let dump = root.join("rx-dump.json");
+        let entries: Vec<String> = operations.iter()
+            .map(|o| format!("{{\"source\": \"{}\", \"target\": \"{}\"}}", o.source.display(), o.target.display()))
+            .collect();
+        fs::write(&dump, format!("{{\"date\": \"\", \"operations\": [{}]}}", entries.join(", "))).unwrap();
+
+        let undo = revert_operations(&dumpfile::read_from_file(&dump).unwrap().0).unwrap();
+        assert_eq!(undo.len(), operations.len());
+        assert_eq!(undo[0].source, operations[2].target);
+        assert_eq!(undo[0].target, operations[2].source);
+        apply(&undo);
+
+        assert_eq!(fs::read_to_string(root.join("Foo/a.txt")).unwrap(), "Foo/a.txt");
+        assert_eq!(fs::read_to_string(root.join("b.txt")).unwrap(), "b.txt");
+        assert!(!root.join("Bar").exists());
+    }
+}
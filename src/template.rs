@@ -0,0 +1,433 @@
+//template replacement language
+//
+// {name}            value of a variable
+// {name:arg}        variable with an argument, e.g. {track:02} or {mtime:%Y}
+// {name|fallback}   fallback template used when the variable has no value
+// {?name:body}      body template rendered only when the variable has a value
+// {{ and }}         literal braces
+
+use regex::Captures;
+use std::fmt;
+use std::path::Path;
+
+
+
+/** data available to the providers for the path being renamed */
+pub struct Context<'a> {
+    pub path: &'a Path,
+    pub root: &'a Path,
+    pub captures: Option<&'a Captures<'a>>,
+    pub index: usize,
+}
+
+
+
+/** source of template variables */
+pub trait Provider: Send + Sync {
+    /** whether name is a variable of this provider, checked when the template is compiled */
+    fn provides(&self, name: &str) -> bool;
+
+    /** validate the argument given to name, checked when the template is compiled */
+    fn check(&self, _name: &str, _arg: Option<&str>) -> Result<(), String> {
+        Ok(())
+    }
+
+    /** value of name for the path being renamed, None when it is not available */
+    fn value(&self, name: &str, arg: Option<&str>, context: &Context) -> Option<String>;
+}
+
+
+
+#[derive(Debug, PartialEq)]
+pub struct TemplateError {
+    pub column: usize,
+    pub message: String,
+}
+
+
+impl TemplateError {
+    //show the template with a pointer under the faulty column
+    pub fn pointer(&self, template: &str) -> String {
+        format!("{}\n{}^ {}", template, " ".repeat(self.column - 1), self.message)
+    }
+}
+
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "column {}: {}", self.column, self.message)
+    }
+}
+
+
+
+#[derive(Debug)]
+enum Node {
+    Text(String),
+    Variable {
+        provider: usize,
+        name: String,
+        arg: Option<String>,
+        fallback: Option<Vec<Node>>,
+    },
+    Condition {
+        provider: usize,
+        name: String,
+        body: Vec<Node>,
+    },
+}
+
+
+
+pub struct Template {
+    nodes: Vec<Node>,
+    providers: Vec<Box<dyn Provider>>,
+}
+
+
+impl Template {
+    /** parse the template and resolve every variable against the providers */
+    pub fn compile(template: &str, providers: Vec<Box<dyn Provider>>) -> Result<Template, TemplateError> {
+        let mut parser = Parser {
+            chars: template.chars().collect(),
+            position: 0,
+            providers: &providers,
+        };
+
+        let nodes = parser.parse_nodes(false)?;
+
+        Ok(Template { nodes, providers })
+    }
+
+
+    /** render the template, fails when a variable has no value and no fallback */
+    pub fn render(&self, context: &Context) -> Result<String, String> {
+        self.render_nodes(&self.nodes, context)
+    }
+
+
+    fn render_nodes(&self, nodes: &[Node], context: &Context) -> Result<String, String> {
+        let mut rendered = String::new();
+
+        for node in nodes {
+            match node {
+                Node::Text(text) => rendered.push_str(text),
+
+                Node::Variable { provider, name, arg, fallback } => {
+                    match self.lookup(*provider, name, arg.as_deref(), context) {
+                        Some(value) => rendered.push_str(&value),
+                        None => match fallback {
+                            Some(fallback) => rendered.push_str(&self.render_nodes(fallback, context)?),
+                            None => return Err(format!("no value for '{{{}}}'", name)),
+                        },
+                    }
+                }
+
+                Node::Condition { provider, name, body } => {
+                    if self.lookup(*provider, name, None, context).is_some() {
+                        rendered.push_str(&self.render_nodes(body, context)?);
+                    }
+                }
+            }
+        }
+
+        Ok(rendered)
+    }
+
+
+    //empty values count as missing, so fallbacks and conditions apply to them too
+    fn lookup(&self, provider: usize, name: &str, arg: Option<&str>, context: &Context) -> Option<String> {
+        self.providers[provider]
+            .value(name, arg, context)
+            .filter(|value| !value.is_empty())
+    }
+}
+
+
+
+struct Parser<'a> {
+    chars: Vec<char>,
+    position: usize,
+    providers: &'a [Box<dyn Provider>],
+}
+
+
+impl Parser<'_> {
+    fn error<T>(&self, position: usize, message: String) -> Result<T, TemplateError> {
+        Err(TemplateError {
+            column: position + 1,
+            message,
+        })
+    }
+
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).cloned()
+    }
+
+
+    //parse until the end of the template, or until the closing brace when nested
+    fn parse_nodes(&mut self, nested: bool) -> Result<Vec<Node>, TemplateError> {
+        let mut nodes = Vec::new();
+        let mut text = String::new();
+
+        while let Some(c) = self.peek() {
+            let next = self.chars.get(self.position + 1).cloned();
+
+            match (c, next) {
+                ('{', Some('{')) | ('}', Some('}')) if !nested || c == '{' => {
+                    text.push(c);
+                    self.position += 2;
+                }
+
+                ('{', _) => {
+                    if !text.is_empty() {
+                        nodes.push(Node::Text(text.split_off(0)));
+                    }
+
+                    nodes.push(self.parse_expression()?);
+                }
+
+                ('}', _) => {
+                    if nested {
+                        break;
+                    }
+
+                    return self.error(self.position, String::from("unmatched '}', use '}}' for a literal brace"));
+                }
+
+                _ => {
+                    text.push(c);
+                    self.position += 1;
+                }
+            }
+        }
+
+        if !text.is_empty() {
+            nodes.push(Node::Text(text));
+        }
+
+        Ok(nodes)
+    }
+
+
+    fn parse_expression(&mut self) -> Result<Node, TemplateError> {
+        let open = self.position;
+        self.position += 1;
+
+        let condition = self.peek() == Some('?');
+        if condition {
+            self.position += 1;
+        }
+
+        let name_start = self.position;
+        let name = self.parse_name()?;
+        let provider = self.resolve(&name, name_start)?;
+
+        if condition {
+            if self.peek() != Some(':') {
+                return self.error(self.position, String::from("expected ':' after the condition"));
+            }
+
+            self.position += 1;
+            let body = self.parse_nodes(true)?;
+            self.close(open)?;
+
+            return Ok(Node::Condition { provider, name, body });
+        }
+
+        let mut arg = None;
+        if self.peek() == Some(':') {
+            self.position += 1;
+            let arg_start = self.position;
+            let mut value = String::new();
+
+            while let Some(c) = self.peek() {
+                match c {
+                    '}' | '|' => break,
+                    '{' => return self.error(self.position, String::from("unexpected '{' in argument")),
+                    _ => value.push(c),
+                }
+                self.position += 1;
+            }
+
+            if let Err(message) = self.providers[provider].check(&name, Some(&value)) {
+                return self.error(arg_start, message);
+            }
+
+            arg = Some(value);
+        } else if let Err(message) = self.providers[provider].check(&name, None) {
+            return self.error(name_start, message);
+        }
+
+        let mut fallback = None;
+        if self.peek() == Some('|') {
+            self.position += 1;
+            fallback = Some(self.parse_nodes(true)?);
+        }
+
+        self.close(open)?;
+
+        Ok(Node::Variable { provider, name, arg, fallback })
+    }
+
+
+    fn parse_name(&mut self) -> Result<String, TemplateError> {
+        let mut name = String::new();
+
+        while let Some(c) = self.peek() {
+            if c.is_alphanumeric() || c == '_' {
+                name.push(c);
+                self.position += 1;
+            } else {
+                break;
+            }
+        }
+
+        if name.is_empty() {
+            return match self.peek() {
+                Some(c) if c != '}' && c != ':' && c != '|' => {
+                    self.error(self.position, format!("invalid character '{}' in variable name", c))
+                }
+                _ => self.error(self.position, String::from("empty variable name")),
+            };
+        }
+
+        Ok(name)
+    }
+
+
+    fn resolve(&self, name: &str, position: usize) -> Result<usize, TemplateError> {
+        match self.providers.iter().position(|p| p.provides(name)) {
+            Some(index) => Ok(index),
+            None => self.error(position, format!("unknown variable '{}'", name)),
+        }
+    }
+
+
+    fn close(&mut self, open: usize) -> Result<(), TemplateError> {
+        match self.peek() {
+            Some('}') => {
+                self.position += 1;
+                Ok(())
+            }
+            None => self.error(open, String::from("unclosed '{'")),
+            Some(c) => self.error(self.position, format!("unexpected '{}', expected '}}'", c)),
+        }
+    }
+}
+
+
+
+/** pad a numeric value following a width spec, "02" pads with zeros and "2" with spaces */
+pub fn pad_number(value: &str, spec: &str) -> String {
+    let width = spec.parse::<usize>().unwrap_or_default();
+
+    if value.is_empty() || !value.chars().all(|c| c.is_ascii_digit()) {
+        return value.to_string();
+    }
+
+    if spec.starts_with('0') {
+        format!("{:0>width$}", value, width = width)
+    } else {
+        format!("{:>width$}", value, width = width)
+    }
+}
+
+
+/** check a width spec used by pad_number */
+pub fn check_pad(arg: Option<&str>) -> Result<(), String> {
+    match arg {
+        Some(spec) if spec.is_empty() || !spec.chars().all(|c| c.is_ascii_digit()) => {
+            Err(format!("invalid width '{}', expected digits like '02'", spec))
+        }
+        _ => Ok(()),
+    }
+}
+
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::HashMap;
+
+    struct MockProvider(HashMap<&'static str, &'static str>);
+
+    impl Provider for MockProvider {
+        fn provides(&self, name: &str) -> bool {
+            self.0.contains_key(name) || name == "missing" || name == "track"
+        }
+
+        fn check(&self, name: &str, arg: Option<&str>) -> Result<(), String> {
+            if name == "track" {
+                check_pad(arg)
+            } else {
+                Ok(())
+            }
+        }
+
+        fn value(&self, name: &str, arg: Option<&str>, _context: &Context) -> Option<String> {
+            let value = self.0.get(name)?;
+            Some(arg.map(|spec| pad_number(value, spec)).unwrap_or_else(|| value.to_string()))
+        }
+    }
+
+
+    fn compile(template: &str) -> Result<Template, TemplateError> {
+        let values = vec![("artist", "Artist"), ("album", "Album"), ("track", "7"), ("title", "Song"), ("disc", "")];
+        Template::compile(template, vec![Box::new(MockProvider(values.into_iter().collect()))])
+    }
+
+
+    fn render(template: &str) -> Result<String, String> {
+        let context = Context {
+            path: Path::new("file.mp3"),
+            root: Path::new(""),
+            captures: None,
+            index: 0,
+        };
+
+        compile(template).unwrap().render(&context)
+    }
+
+
+    #[test]
+    fn render_variables() {
+        assert_eq!(render("{artist}/{album}/{track:02} {title}").unwrap(), "Artist/Album/07 Song");
+        assert_eq!(render("{{{title}}}").unwrap(), "{Song}");
+    }
+
+
+    #[test]
+    fn render_fallbacks_and_conditions() {
+        assert_eq!(render("{missing|Unknown {artist}}").unwrap(), "Unknown Artist");
+        assert_eq!(render("{title}{?disc: (Disc {disc})}").unwrap(), "Song");
+        assert_eq!(render("{title}{?track: (Track {track})}").unwrap(), "Song (Track 7)");
+        assert_eq!(render("{disc}").unwrap_err(), "no value for '{disc}'");
+    }
+
+
+    #[test]
+    fn errors_point_at_column() {
+        let error = |template| compile(template).err().unwrap();
+
+        assert_eq!(error("{artist").column, 1);
+        assert_eq!(error("{artist} - {unknown}").column, 13);
+        assert_eq!(error("{track:x2}").column, 8);
+        assert_eq!(error("a}").column, 2);
+        assert_eq!(error("{title-x}").message, "unexpected '-', expected '}'");
+        assert_eq!(error("{}").message, "empty variable name");
+        assert_eq!(error("{?title}").message, "expected ':' after the condition");
+        assert_eq!(error("{title").pointer("{title"), "{title\n^ unclosed '{'");
+    }
+
+
+    #[test]
+    fn pad_numbers() {
+        assert_eq!(pad_number("7", "02"), "07");
+        assert_eq!(pad_number("7", "3"), "  7");
+        assert_eq!(pad_number("123", "02"), "123");
+        assert_eq!(pad_number("abc", "04"), "abc");
+    }
+}
@@ -0,0 +1,207 @@
+//UTF-8 -> ASCII transliteration with locale and user tables
+
+use any_ascii::any_ascii;
+use regex::Regex;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+
+
+pub const LOCALES: [&str; 7] = ["da", "de", "fr", "nb", "no", "ru", "uk"];
+
+
+//common conventions that differ from the any_ascii defaults
+fn locale_table(locale: &str) -> &'static [(char, &'static str)] {
+    match locale {
+        "de" => &[
+            ('ä', "ae"), ('ö', "oe"), ('ü', "ue"), ('ß', "ss"),
+            ('Ä', "Ae"), ('Ö', "Oe"), ('Ü', "Ue"), ('ẞ', "SS"),
+        ],
+        "da" | "nb" | "no" => &[
+            ('æ', "ae"), ('ø', "oe"), ('å', "aa"),
+            ('Æ', "Ae"), ('Ø', "Oe"), ('Å', "Aa"),
+        ],
+        "fr" => &[
+            ('œ', "oe"), ('Œ', "OE"), ('æ', "ae"), ('Æ', "AE"),
+            ('«', ""), ('»', ""),
+        ],
+        //ICAO passport transliteration
+        "ru" => &[
+            ('й', "i"), ('ъ', "ie"), ('ь', ""), ('ю', "iu"), ('я', "ia"),
+            ('Й', "I"), ('Ъ', "IE"), ('Ь', ""), ('Ю', "Iu"), ('Я', "Ia"),
+        ],
+        //Ukrainian national transliteration
+        "uk" => &[
+            ('г', "h"), ('и', "y"), ('й', "i"), ('ь', ""), ('ю', "iu"), ('я', "ia"), ('є', "ie"), ('ї', "i"),
+            ('Г', "H"), ('И', "Y"), ('Й', "I"), ('Ь', ""), ('Ю', "Iu"), ('Я', "Ia"), ('Є', "Ie"), ('Ї', "I"),
+        ],
+        _ => &[],
+    }
+}
+
+
+
+#[derive(Default)]
+pub struct Transliterator {
+    table: HashMap<char, String>,
+    keep: Option<Regex>,
+}
+
+
+impl Transliterator {
+    /** locale table first, the user table overrides it */
+    pub fn new(locale: Option<&str>, map_file: Option<&Path>, keep: Option<&str>) -> Result<Transliterator, String> {
+        let mut table: HashMap<char, String> = locale
+            .map(locale_table)
+            .unwrap_or_default()
+            .iter()
+            .map(|(c, s)| (*c, s.to_string()))
+            .collect();
+
+        if let Some(map_file) = map_file {
+            table.extend(read_map_file(map_file)?);
+        }
+
+        let keep = match keep {
+            Some(class) => Some(compile_class(class)?),
+            None => None,
+        };
+
+        Ok(Transliterator { table, keep })
+    }
+
+
+    /** transliterate a name, returning every replaced char with its replacement */
+    pub fn apply(&self, name: &str) -> (String, Vec<(char, String)>) {
+        let chars: Vec<char> = name.chars().collect();
+        let mut ascii = String::new();
+        let mut replaced = Vec::new();
+
+        for (index, c) in chars.iter().enumerate() {
+            if c.is_ascii() || self.keep(*c) {
+                ascii.push(*c);
+                continue;
+            }
+
+            let mut replacement = match self.table.get(c) {
+                Some(replacement) => replacement.clone(),
+                None => any_ascii(&c.to_string()),
+            };
+
+            //"ÄRGER" -> "AERGER" rather than "AeRGER"
+            let next_upper = chars.get(index + 1).map(|n| n.is_uppercase()).unwrap_or(false);
+            if c.is_uppercase() && next_upper {
+                replacement = replacement.to_uppercase();
+            }
+
+            ascii.push_str(&replacement);
+            replaced.push((*c, replacement));
+        }
+
+        (ascii, replaced)
+    }
+
+
+    fn keep(&self, c: char) -> bool {
+        match &self.keep {
+            Some(keep) => keep.is_match(c.encode_utf8(&mut [0; 4])),
+            None => false,
+        }
+    }
+}
+
+
+
+//"£€" is read as "[£€]", anything starting with '[' or '\' is used as is
+fn compile_class(class: &str) -> Result<Regex, String> {
+    let class = if class.starts_with('[') || class.starts_with('\\') {
+        class.to_string()
+    } else {
+        format!("[{}]", regex::escape(class))
+    };
+
+    match Regex::new(&format!("^(?:{})$", class)) {
+        Ok(regex) => Ok(regex),
+        Err(err) => Err(format!("Bad character class provided\n\n {}", err)),
+    }
+}
+
+
+
+/** one "char=replacement" per line, '#' starts a comment */
+fn read_map_file(path: &Path) -> Result<HashMap<char, String>, String> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(err) => return Err(format!("Cannot read mapping file {}\n\n {}", path.display(), err)),
+    };
+
+    parse_map(&contents).map_err(|err| format!("Bad mapping file {}\n\n {}", path.display(), err))
+}
+
+
+fn parse_map(contents: &str) -> Result<HashMap<char, String>, String> {
+    let mut table = HashMap::new();
+
+    for (line_number, line) in contents.lines().enumerate() {
+        if line.trim().is_empty() || line.trim_start().starts_with('#') {
+            continue;
+        }
+
+        let mut chars = line.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), Some('=')) => {
+                table.insert(c, chars.as_str().to_string());
+            }
+            _ => return Err(format!("line {}: expected 'char=replacement'", line_number + 1)),
+        }
+    }
+
+    Ok(table)
+}
+
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+
+    #[test]
+    fn locale_tables() {
+        let german = Transliterator::new(Some("de"), None, None).unwrap();
+        assert_eq!(german.apply("Größe Übung ÄRGER").0, "Groesse Uebung AERGER");
+
+        let default = Transliterator::default();
+        assert_eq!(default.apply("Größe").0, "Grosse");
+
+        let russian = Transliterator::new(Some("ru"), None, None).unwrap();
+        assert_eq!(russian.apply("Юлия Ильич").0, "Iuliia Ilich");
+    }
+
+
+    #[test]
+    fn keep_and_report() {
+        let transliterator = Transliterator::new(None, None, Some("£")).unwrap();
+        let (ascii, replaced) = transliterator.apply("£5 café");
+
+        assert_eq!(ascii, "£5 cafe");
+        assert_eq!(replaced, vec![('é', String::from("e"))]);
+
+        let emoji = Transliterator::new(None, None, Some(r"\p{Emoji_Presentation}")).unwrap();
+        assert_eq!(emoji.apply("party 🎉 ü").0, "party 🎉 u");
+    }
+
+
+    #[test]
+    fn user_map_overrides_locale() {
+        let table = parse_map("# comment\nä=a\n€=EUR\n✓=\n").unwrap();
+        assert_eq!(table[&'ä'], "a");
+        assert_eq!(table[&'✓'], "");
+        assert!(parse_map("ab").is_err());
+
+        let mut transliterator = Transliterator::new(Some("de"), None, None).unwrap();
+        transliterator.table.extend(table);
+        assert_eq!(transliterator.apply("ä ✓ ü").0, "a  ue");
+    }
+}
@@ -0,0 +1,536 @@
+//video container metadata
+//
+// {video:created}    creation time, with an optional format, e.g. {video:created:%Y%m%d}
+// {video:duration}   duration like 1h02m05s
+// {video:WxH}        display dimensions like 1920x1080
+//
+// MP4/MOV files are read from the mvhd and tkhd boxes, MKV/WebM files from the
+// segment Info and Tracks elements.
+
+use crate::providers::{check_key, split_key, FileCache};
+use crate::template::{Context, Provider};
+use chrono::{DateTime, Duration, Local, TimeZone, Utc};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+
+
+const KEYS: [&str; 3] = ["created", "duration", "WxH"];
+const DATES: [&str; 1] = ["created"];
+
+//seconds between 1904-01-01 (ISO-BMFF epoch) and 1970-01-01
+const MP4_EPOCH_OFFSET: i64 = 2_082_844_800;
+//seconds between 1970-01-01 and 2001-01-01 (Matroska epoch)
+const MKV_EPOCH_OFFSET: i64 = 978_307_200;
+
+//Matroska element ids
+const EBML: u32 = 0x1A45_DFA3;
+const SEGMENT: u32 = 0x1853_8067;
+const CLUSTER: u32 = 0x1F43_B675;
+const INFO: u32 = 0x1549_A966;
+const TIMECODE_SCALE: u32 = 0x2A_D7B1;
+const DURATION: u32 = 0x4489;
+const DATE_UTC: u32 = 0x4461;
+const TRACKS: u32 = 0x1654_AE6B;
+const TRACK_ENTRY: u32 = 0xAE;
+const TRACK_TYPE: u32 = 0x83;
+const VIDEO: u32 = 0xE0;
+const PIXEL_WIDTH: u32 = 0xB0;
+const PIXEL_HEIGHT: u32 = 0xBA;
+
+
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VideoInfo {
+    pub created: Option<DateTime<Utc>>,
+    pub duration: Option<f64>,
+    pub dimensions: Option<(u32, u32)>,
+}
+
+
+
+/** {video:created}, {video:duration} and {video:WxH} of MP4, MOV and MKV files */
+#[derive(Default)]
+pub struct VideoProvider {
+    cache: FileCache<VideoInfo>,
+}
+
+
+impl Provider for VideoProvider {
+    fn provides(&self, name: &str) -> bool {
+        name == "video"
+    }
+
+    fn check(&self, _name: &str, arg: Option<&str>) -> Result<(), String> {
+        check_key(arg, &KEYS, &DATES)
+    }
+
+    fn value(&self, _name: &str, arg: Option<&str>, context: &Context) -> Option<String> {
+        let info = self.cache.get(context.path, read_video)?;
+        let (key, format) = split_key(arg?);
+
+        match key {
+            "created" => {
+                let created = info.created?.with_timezone(&Local);
+                Some(created.format(format.unwrap_or("%Y-%m-%d")).to_string())
+            }
+            "duration" => Some(format_duration(info.duration?)),
+            "WxH" => info.dimensions.map(|(width, height)| format!("{}x{}", width, height)),
+            _ => None,
+        }
+    }
+}
+
+
+
+//"1h02m05s", "2m05s" or "5s"
+fn format_duration(seconds: f64) -> String {
+    let total = seconds.round() as u64;
+    let (hours, minutes, seconds) = (total / 3600, total / 60 % 60, total % 60);
+
+    if hours > 0 {
+        format!("{}h{:02}m{:02}s", hours, minutes, seconds)
+    } else if minutes > 0 {
+        format!("{}m{:02}s", minutes, seconds)
+    } else {
+        format!("{}s", seconds)
+    }
+}
+
+
+pub fn read_video(path: &Path) -> Option<VideoInfo> {
+    let mut file = File::open(path).ok()?;
+
+    let mut magic = [0; 8];
+    file.read_exact(&mut magic).ok()?;
+    file.seek(SeekFrom::Start(0)).ok()?;
+
+    if magic[..4] == EBML.to_be_bytes() {
+        read_matroska(&mut file)
+    } else if [b"ftyp", b"moov", b"mdat", b"wide", b"free"].iter().any(|t| magic[4..] == t[..]) {
+        read_mp4(&mut file)
+    } else {
+        None
+    }
+}
+
+
+
+/** top level boxes are skipped until moov, which is small enough to be read at once */
+fn read_mp4<R: Read + Seek>(reader: &mut R) -> Option<VideoInfo> {
+    loop {
+        let mut header = [0; 8];
+        reader.read_exact(&mut header).ok()?;
+
+        let mut size = u64::from(u32::from_be_bytes([header[0], header[1], header[2], header[3]]));
+        let mut header_size = 8;
+        if size == 1 {
+            let mut large = [0; 8];
+            reader.read_exact(&mut large).ok()?;
+            size = u64::from_be_bytes(large);
+            header_size = 16;
+        }
+
+        if &header[4..] == b"moov" {
+            if size == 0 {
+                let mut moov = Vec::new();
+                reader.read_to_end(&mut moov).ok()?;
+                return Some(parse_moov(&moov));
+            }
+
+            let moov = read_payload(reader, size.checked_sub(header_size)?)?;
+            return Some(parse_moov(&moov));
+        }
+
+        //a zero size box runs to the end of the file
+        if size == 0 || size < header_size {
+            return None;
+        }
+
+        reader.seek(SeekFrom::Current((size - header_size) as i64)).ok()?;
+    }
+}
+
+
+//sizes come from the file, a payload running past its end is corrupt and not allocated
+fn read_payload<R: Read + Seek>(reader: &mut R, size: u64) -> Option<Vec<u8>> {
+    let position = reader.stream_position().ok()?;
+    let end = reader.seek(SeekFrom::End(0)).ok()?;
+    reader.seek(SeekFrom::Start(position)).ok()?;
+    if size > end.saturating_sub(position) {
+        return None;
+    }
+
+    let mut payload = vec![0; size as usize];
+    reader.read_exact(&mut payload).ok()?;
+    Some(payload)
+}
+
+
+//(type, payload) of the boxes directly inside data
+fn boxes(data: &[u8]) -> Vec<(&[u8], &[u8])> {
+    let mut boxes = Vec::new();
+    let mut position = 0;
+
+    while position + 8 <= data.len() {
+        let size = read_u32(data, position).unwrap_or(0) as usize;
+        let kind = &data[position + 4..position + 8];
+
+        let (start, end) = match size {
+            0 => (position + 8, data.len()),
+            1 => match read_u64(data, position + 8) {
+                Some(large) => (position + 16, position.saturating_add(large as usize)),
+                None => break,
+            },
+            _ => (position + 8, position.saturating_add(size)),
+        };
+
+        if end > data.len() || end < start {
+            break;
+        }
+
+        boxes.push((kind, &data[start..end]));
+        position = end;
+    }
+
+    boxes
+}
+
+
+fn parse_moov(moov: &[u8]) -> VideoInfo {
+    let mut info = VideoInfo::default();
+
+    for (kind, payload) in boxes(moov) {
+        match kind {
+            b"mvhd" => {
+                //version 1 uses 64 bit times and duration
+                let parsed = if payload.first() == Some(&1) {
+                    read_u64(payload, 4).zip(read_u32(payload, 20)).zip(read_u64(payload, 24))
+                } else {
+                    read_u32(payload, 4).map(u64::from).zip(read_u32(payload, 12)).zip(read_u32(payload, 16).map(u64::from))
+                };
+
+                if let Some(((created, timescale), duration)) = parsed {
+                    info.created = mp4_time(created);
+                    if timescale > 0 {
+                        info.duration = Some(duration as f64 / f64::from(timescale));
+                    }
+                }
+            }
+            b"trak" if info.dimensions.is_none() => {
+                info.dimensions = boxes(payload)
+                    .into_iter()
+                    .find(|(kind, _)| kind == b"tkhd")
+                    .and_then(|(_, tkhd)| tkhd_dimensions(tkhd));
+            }
+            _ => {}
+        }
+    }
+
+    info
+}
+
+
+//audio tracks have no dimensions, rotated tracks have their width and height swapped
+fn tkhd_dimensions(tkhd: &[u8]) -> Option<(u32, u32)> {
+    let offset = if tkhd.first() == Some(&1) { 88 } else { 76 };
+    let matrix = offset - 36;
+
+    let width = read_u32(tkhd, offset)? >> 16;
+    let height = read_u32(tkhd, offset + 4)? >> 16;
+    if width == 0 || height == 0 {
+        return None;
+    }
+
+    let a = read_u32(tkhd, matrix)?;
+    let b = read_u32(tkhd, matrix + 4)?;
+    if a == 0 && b != 0 {
+        return Some((height, width));
+    }
+
+    Some((width, height))
+}
+
+
+fn mp4_time(seconds: u64) -> Option<DateTime<Utc>> {
+    if seconds == 0 {
+        return None;
+    }
+
+    Utc.timestamp_opt(seconds as i64 - MP4_EPOCH_OFFSET, 0).single()
+}
+
+
+fn read_u32(data: &[u8], position: usize) -> Option<u32> {
+    let bytes = data.get(position..position + 4)?;
+    Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+
+fn read_u64(data: &[u8], position: usize) -> Option<u64> {
+    let bytes = data.get(position..position + 8)?;
+    let mut array = [0; 8];
+    array.copy_from_slice(bytes);
+    Some(u64::from_be_bytes(array))
+}
+
+
+
+/** Info and Tracks are read from the segment, clusters hold the frames and end the search */
+fn read_matroska<R: Read + Seek>(reader: &mut R) -> Option<VideoInfo> {
+    let (id, size) = read_element_header(reader)?;
+    if id != EBML {
+        return None;
+    }
+    reader.seek(SeekFrom::Current(size? as i64)).ok()?;
+
+    let (id, _) = read_element_header(reader)?;
+    if id != SEGMENT {
+        return None;
+    }
+
+    let mut info = VideoInfo::default();
+    let mut found = false;
+
+    while let Some((id, size)) = read_element_header(reader) {
+        match (id, size) {
+            (CLUSTER, _) | (_, None) => break,
+            (INFO, Some(size)) | (TRACKS, Some(size)) => {
+                let payload = read_payload(reader, size)?;
+
+                if id == INFO {
+                    parse_info(&payload, &mut info);
+                } else {
+                    info.dimensions = parse_tracks(&payload);
+                }
+
+                found = true;
+            }
+            (_, Some(size)) => {
+                reader.seek(SeekFrom::Current(size as i64)).ok()?;
+            }
+        }
+    }
+
+    if found {
+        Some(info)
+    } else {
+        None
+    }
+}
+
+
+fn parse_info(payload: &[u8], info: &mut VideoInfo) {
+    let mut scale = 1_000_000;
+    let mut duration = None;
+
+    for (id, data) in elements(payload) {
+        match id {
+            TIMECODE_SCALE => scale = read_uint(data),
+            DURATION => duration = read_float(data),
+            DATE_UTC => {
+                let nanoseconds = read_uint(data) as i64;
+                info.created = Utc
+                    .timestamp_opt(MKV_EPOCH_OFFSET, 0)
+                    .single()
+                    .map(|epoch| epoch + Duration::nanoseconds(nanoseconds));
+            }
+            _ => {}
+        }
+    }
+
+    info.duration = duration.map(|d| d * scale as f64 / 1e9);
+}
+
+
+fn parse_tracks(payload: &[u8]) -> Option<(u32, u32)> {
+    elements(payload).into_iter().filter(|(id, _)| *id == TRACK_ENTRY).find_map(|(_, entry)| {
+        let entry = elements(entry);
+        if !entry.iter().any(|(id, data)| *id == TRACK_TYPE && read_uint(data) == 1) {
+            return None;
+        }
+
+        let video = elements(entry.iter().find(|(id, _)| *id == VIDEO)?.1);
+        let dimension = |wanted: u32| video.iter().find(|(id, _)| *id == wanted).map(|(_, data)| read_uint(data) as u32);
+
+        Some((dimension(PIXEL_WIDTH)?, dimension(PIXEL_HEIGHT)?))
+    })
+}
+
+
+//(id, payload) of the elements directly inside data
+fn elements(data: &[u8]) -> Vec<(u32, &[u8])> {
+    let mut elements = Vec::new();
+    let mut reader = std::io::Cursor::new(data);
+
+    while let Some((id, Some(size))) = read_element_header(&mut reader) {
+        let start = reader.position() as usize;
+        let end = match start.checked_add(size as usize) {
+            Some(end) if end <= data.len() => end,
+            _ => break,
+        };
+
+        elements.push((id, &data[start..end]));
+        reader.set_position(end as u64);
+    }
+
+    elements
+}
+
+
+//the id keeps its length marker, the size drops it and is None when unknown
+fn read_element_header<R: Read>(reader: &mut R) -> Option<(u32, Option<u64>)> {
+    let (id, length) = read_vint(reader)?;
+    if length > 4 {
+        return None;
+    }
+
+    let (size, size_length) = read_vint(reader)?;
+    let value = size & ((1 << (7 * size_length)) - 1);
+    let unknown = value == (1 << (7 * size_length)) - 1;
+
+    Some((id as u32, if unknown { None } else { Some(value) }))
+}
+
+
+fn read_vint<R: Read>(reader: &mut R) -> Option<(u64, u32)> {
+    let mut first = [0; 1];
+    reader.read_exact(&mut first).ok()?;
+
+    let length = first[0].leading_zeros() + 1;
+    if length > 8 {
+        return None;
+    }
+
+    let mut value = u64::from(first[0]);
+    for _ in 1..length {
+        let mut byte = [0; 1];
+        reader.read_exact(&mut byte).ok()?;
+        value = (value << 8) | u64::from(byte[0]);
+    }
+
+    Some((value, length))
+}
+
+
+fn read_uint(data: &[u8]) -> u64 {
+    data.iter().take(8).fold(0, |value, byte| (value << 8) | u64::from(*byte))
+}
+
+
+fn read_float(data: &[u8]) -> Option<f64> {
+    match data.len() {
+        4 => Some(f64::from(f32::from_bits(read_uint(data) as u32))),
+        8 => Some(f64::from_bits(read_uint(data))),
+        _ => None,
+    }
+}
+
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Cursor;
+
+
+    fn mp4_box(kind: &[u8], payload: &[u8]) -> Vec<u8> {
+        let mut data = ((payload.len() + 8) as u32).to_be_bytes().to_vec();
+        data.extend_from_slice(kind);
+        data.extend_from_slice(payload);
+        data
+    }
+
+
+    fn tkhd(width: u32, height: u32, rotated: bool) -> Vec<u8> {
+        let mut payload = vec![0; 84];
+        let (a, b) = if rotated { (0, 0x0001_0000) } else { (0x0001_0000, 0) };
+        payload[40..44].copy_from_slice(&(a as u32).to_be_bytes());
+        payload[44..48].copy_from_slice(&(b as u32).to_be_bytes());
+        payload[76..80].copy_from_slice(&(width << 16).to_be_bytes());
+        payload[80..84].copy_from_slice(&(height << 16).to_be_bytes());
+        mp4_box(b"tkhd", &payload)
+    }
+
+
+    #[test]
+    fn mp4_boxes() {
+        //2024-03-01 12:00:00 UTC, 3725 units at 10 units per second
+        let mut mvhd = vec![0; 20];
+        mvhd[4..8].copy_from_slice(&((1_709_294_400 + MP4_EPOCH_OFFSET) as u32).to_be_bytes());
+        mvhd[12..16].copy_from_slice(&10u32.to_be_bytes());
+        mvhd[16..20].copy_from_slice(&3_725u32.to_be_bytes());
+
+        let mut moov = mp4_box(b"mvhd", &mvhd);
+        moov.extend(mp4_box(b"trak", &tkhd(0, 0, false)));
+        moov.extend(mp4_box(b"trak", &tkhd(1920, 1080, true)));
+
+        let mut file = mp4_box(b"ftyp", b"isom");
+        file.extend(mp4_box(b"mdat", &[0; 32]));
+        file.extend(mp4_box(b"moov", &moov));
+
+        let info = read_mp4(&mut Cursor::new(file)).unwrap();
+        assert_eq!(info.created.unwrap().to_rfc3339(), "2024-03-01T12:00:00+00:00");
+        assert_eq!(format_duration(info.duration.unwrap()), "6m13s");
+        assert_eq!(info.dimensions, Some((1080, 1920)));
+    }
+
+
+    #[test]
+    fn oversized_boxes() {
+        //a moov box claiming 4GB in a 40 bytes file
+        let mut file = mp4_box(b"ftyp", b"isom");
+        file.extend(0xFFFF_FFF0u32.to_be_bytes());
+        file.extend(b"moov");
+        file.extend([0; 20]);
+        assert_eq!(read_mp4(&mut Cursor::new(file)), None);
+
+        //a 64 bit size past the end of the file
+        let mut file = 1u32.to_be_bytes().to_vec();
+        file.extend(b"moov");
+        file.extend(u64::MAX.to_be_bytes());
+        assert_eq!(read_mp4(&mut Cursor::new(file)), None);
+
+        //a segment Info element with an 8 bytes size of 2^48
+        let mut file = vec![0x1A, 0x45, 0xDF, 0xA3, 0x80];
+        file.extend(&[0x18, 0x53, 0x80, 0x67, 0x01, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]);
+        file.extend(&[0x15, 0x49, 0xA9, 0x66, 0x01, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+        file.extend([0; 16]);
+        assert_eq!(read_matroska(&mut Cursor::new(file)), None);
+    }
+
+
+    #[test]
+    fn matroska_elements() {
+        let element = |id: &[u8], payload: &[u8]| {
+            let mut data = id.to_vec();
+            data.push(0x80 | payload.len() as u8);
+            data.extend_from_slice(payload);
+            data
+        };
+
+        let mut info = element(&[0x2A, 0xD7, 0xB1], &[0x0F, 0x42, 0x40]);
+        info.extend(element(&[0x44, 0x89], &3_725_000f64.to_be_bytes()));
+        info.extend(element(&[0x44, 0x61], &0i64.to_be_bytes()));
+
+        let mut video = element(&[0xB0], &[0x07, 0x80]);
+        video.extend(element(&[0xBA], &[0x04, 0x38]));
+        let mut entry = element(&[0x83], &[0x01]);
+        entry.extend(element(&[0xE0], &video));
+        let tracks = element(&[0x16, 0x54, 0xAE, 0x6B], &element(&[0xAE], &entry));
+
+        let mut segment_payload = element(&[0x15, 0x49, 0xA9, 0x66], &info);
+        segment_payload.extend(tracks);
+        segment_payload.extend(element(&[0x1F, 0x43, 0xB6, 0x75], &[0; 4]));
+
+        let mut file = element(&[0x1A, 0x45, 0xDF, 0xA3], &[0x42, 0x86, 0x81, 0x01]);
+        file.extend(&[0x18, 0x53, 0x80, 0x67, 0x01, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]);
+        file.extend(segment_payload);
+
+        let info = read_matroska(&mut Cursor::new(file)).unwrap();
+        assert_eq!(info.created.unwrap().to_rfc3339(), "2001-01-01T00:00:00+00:00");
+        assert_eq!(format_duration(info.duration.unwrap()), "1h02m05s");
+        assert_eq!(info.dimensions, Some((1920, 1080)));
+    }
+}
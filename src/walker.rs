@@ -0,0 +1,441 @@
+//recursive mode walk, with walkdir on the current thread or spread over worker threads
+//
+// Both walks prune and select entries with the same rules and list them sorted by path,
+// so the thread count never changes the planned renames.
+
+use crate::config::WalkRoot;
+use crate::error::{Error, ErrorKind};
+use crate::fileutils::PathList;
+use crate::filter::{PathFilter, Predicates};
+use crate::ignores::IgnoreRules;
+use crate::output::Printer;
+use path_abs::PathAbs;
+use std::collections::HashSet;
+use std::fs::{self, Metadata};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use walkdir::WalkDir;
+
+
+
+pub struct WalkOptions<'a> {
+    pub hidden: bool,
+    pub follow: bool,
+    pub one_file_system: bool,
+    pub filter: &'a PathFilter,
+    pub predicates: &'a Predicates,
+    pub ignore: bool,
+    //the walkdir loop is used for a single thread
+    pub threads: usize,
+}
+
+
+
+/** every root in order, each one sorted by path */
+pub fn walk(roots: &[WalkRoot], options: &WalkOptions, printer: &Printer) -> PathList {
+    let mut path_list = PathList::new();
+
+    //whether each path was reached through a followed directory link
+    let mut linked = Vec::new();
+
+    for root in roots {
+        let walk = RootWalk::new(root, options);
+        let (mut walk_list, crossings) = if options.threads > 1 { walk.parallel() } else { walk.sequential() };
+        if options.follow {
+            linked.extend(walk_list.iter().map(|path| is_under_link(path, Path::new(&root.path))));
+        }
+        path_list.append(&mut walk_list);
+
+        for crossing in crossings {
+            printer.print_warning(&Error {
+                kind: ErrorKind::OtherFilesystem,
+                value: Some(crossing.to_string_lossy().to_string()),
+            });
+        }
+    }
+
+    //a followed directory link lists the entries of a walked directory again, only that copy is dropped,
+    //links are entries of their own even when their target is walked too
+    if options.follow {
+        let mut visited: HashSet<PathBuf> = path_list.iter().zip(&linked).filter(|(_, linked)| !**linked).map(|(path, _)| entry_path(path)).collect();
+        let mut linked = linked.into_iter();
+        path_list.retain(|path| !linked.next().unwrap_or(false) || visited.insert(entry_path(path)));
+    }
+
+    path_list
+}
+
+
+//a directory between the root and the path is a link
+fn is_under_link(path: &Path, root: &Path) -> bool {
+    path.ancestors()
+        .skip(1)
+        .take_while(|ancestor| *ancestor != root && ancestor.starts_with(root))
+        .any(|ancestor| fs::symlink_metadata(ancestor).map(|m| m.file_type().is_symlink()).unwrap_or(false))
+}
+
+
+//the entry a path names, its own link is not resolved
+fn entry_path(path: &Path) -> PathBuf {
+    match (path.parent().and_then(|parent| fs::canonicalize(parent).ok()), path.file_name()) {
+        (Some(parent), Some(name)) => parent.join(name),
+        _ => path.to_path_buf(),
+    }
+}
+
+
+
+struct RootWalk<'a> {
+    root: &'a WalkRoot,
+    options: &'a WalkOptions<'a>,
+    //ignore files are matched against absolute paths, the root parents may hold some
+    absolute: PathBuf,
+    device: Option<u64>,
+}
+
+
+impl<'a> RootWalk<'a> {
+    fn new(root: &'a WalkRoot, options: &'a WalkOptions<'a>) -> RootWalk<'a> {
+        RootWalk {
+            root,
+            options,
+            absolute: PathAbs::new(&root.path).map(|p| p.to_path_buf()).unwrap_or_else(|_| PathBuf::from(&root.path)),
+            device: fs::metadata(&root.path).ok().and_then(|m| device_id(&m)),
+        }
+    }
+
+
+    //filters match the path relative to the root
+    fn relative<'p>(&self, path: &'p Path) -> &'p Path {
+        path.strip_prefix(&self.root.path).unwrap_or(path)
+    }
+
+
+    fn ignore_rules(&self) -> Option<IgnoreRules> {
+        if self.options.ignore { Some(IgnoreRules::new(&self.absolute)) } else { None }
+    }
+
+
+    /** hidden, excluded and ignored entries are skipped and not walked into, like other filesystems */
+    fn is_walked(&self, path: &Path, depth: usize, is_dir: bool, rules: Option<&mut IgnoreRules>, crossings: &mut PathList) -> bool {
+        if depth == 0 {
+            return true;
+        }
+
+        let hidden = path.file_name().and_then(|name| name.to_str()).map(|name| name.starts_with('.')).unwrap_or(true);
+        if hidden && !self.options.hidden {
+            return false;
+        }
+
+        let relative = self.relative(path);
+        if self.options.filter.is_excluded(relative) {
+            return false;
+        }
+
+        if let Some(rules) = rules {
+            if rules.is_ignored(&self.absolute.join(relative), is_dir) {
+                return false;
+            }
+        }
+
+        if self.options.one_file_system && is_dir {
+            let metadata = if self.options.follow { fs::metadata(path) } else { fs::symlink_metadata(path) };
+            let device = metadata.ok().and_then(|m| device_id(&m));
+            if device.is_some() && device != self.device {
+                crossings.push(path.to_path_buf());
+                return false;
+            }
+        }
+
+        true
+    }
+
+
+    /** directories left out here are still walked */
+    fn is_listed(&self, path: &Path, depth: usize, metadata: &Metadata) -> bool {
+        depth >= self.root.min_depth
+            && self.options.filter.is_selected(self.relative(path))
+            && self.options.predicates.matches(path, metadata)
+    }
+
+
+    fn is_walked_into(&self, depth: usize) -> bool {
+        self.root.max_depth.map(|max_depth| depth < max_depth).unwrap_or(true)
+    }
+
+
+    //walkdir reports links back to an ancestor as errors instead of looping, they are skipped
+    fn sequential(&self) -> (PathList, PathList) {
+        let mut rules = self.ignore_rules();
+        let mut crossings = PathList::new();
+
+        let walkdir = WalkDir::new(&self.root.path).follow_links(self.options.follow).sort_by_file_name();
+        let walkdir = match self.root.max_depth {
+            Some(max_depth) => walkdir.max_depth(max_depth),
+            None => walkdir,
+        };
+
+        let walk_list = walkdir
+            .into_iter()
+            .filter_entry(|e| {
+                let is_dir = e.file_type().is_dir();
+                if !self.is_walked(e.path(), e.depth(), is_dir, rules.as_mut(), &mut crossings) {
+                    return false;
+                }
+
+                if let (Some(rules), true) = (rules.as_mut(), is_dir) {
+                    rules.enter(&self.absolute.join(self.relative(e.path())));
+                }
+                true
+            })
+            .filter_map(|e| e.ok())
+            .filter(|e| e.metadata().map(|m| self.is_listed(e.path(), e.depth(), &m)).unwrap_or(false))
+            .map(|e| e.path().to_path_buf())
+            .collect();
+
+        (walk_list, crossings)
+    }
+
+
+    //directories are shared out to the workers, each one carrying the ignore rules and ancestors above it
+    fn parallel(&self) -> (PathList, PathList) {
+        let root = Path::new(&self.root.path);
+        let metadata = match if self.options.follow { fs::metadata(root) } else { fs::symlink_metadata(root) } {
+            Ok(metadata) => metadata,
+            Err(_) => return (PathList::new(), PathList::new()),
+        };
+
+        let mut walk_list = PathList::new();
+        if self.is_listed(root, 0, &metadata) {
+            walk_list.push(root.to_path_buf());
+        }
+        if !metadata.is_dir() || !self.is_walked_into(0) {
+            return (walk_list, PathList::new());
+        }
+
+        let mut rules = self.ignore_rules();
+        if let Some(rules) = rules.as_mut() {
+            rules.enter(&self.absolute);
+        }
+        let ancestors = if self.options.follow { vec![fs::canonicalize(root).unwrap_or_else(|_| root.to_path_buf())] } else { Vec::new() };
+
+        let queue = JobQueue::new(Job { dir: root.to_path_buf(), depth: 0, rules, ancestors: Arc::new(ancestors) });
+        let crossings = Mutex::new(PathList::new());
+        let listed = Mutex::new(walk_list);
+
+        thread::scope(|scope| {
+            for _ in 0..self.options.threads {
+                scope.spawn(|| {
+                    let mut walk_list = PathList::new();
+                    let mut walk_crossings = PathList::new();
+                    while let Some(job) = queue.pop() {
+                        let children = self.read_dir(&job, &mut walk_list, &mut walk_crossings);
+                        queue.finish(children);
+                    }
+
+                    listed.lock().unwrap().append(&mut walk_list);
+                    crossings.lock().unwrap().append(&mut walk_crossings);
+                });
+            }
+        });
+
+        let mut walk_list = listed.into_inner().unwrap();
+        let mut crossings = crossings.into_inner().unwrap();
+        walk_list.sort();
+        crossings.sort();
+        (walk_list, crossings)
+    }
+
+
+    //list the entries of a directory, the subdirectories to walk are returned
+    fn read_dir(&self, job: &Job, walk_list: &mut PathList, crossings: &mut PathList) -> Vec<Job> {
+        let entries = match fs::read_dir(&job.dir) {
+            Ok(entries) => entries,
+            Err(_) => return Vec::new(),
+        };
+
+        let depth = job.depth + 1;
+        let mut rules = job.rules.clone();
+        let mut children = Vec::new();
+
+        for entry in entries.filter_map(|e| e.ok()) {
+            let path = entry.path();
+            let mut metadata = match entry.metadata() {
+                Ok(metadata) => metadata,
+                Err(_) => continue,
+            };
+            //walkdir drops the links it cannot follow
+            if self.options.follow && metadata.file_type().is_symlink() {
+                metadata = match fs::metadata(&path) {
+                    Ok(target) => target,
+                    Err(_) => continue,
+                };
+            }
+
+            let is_dir = metadata.is_dir();
+            let mut ancestors = None;
+            if self.options.follow && is_dir {
+                let canonical = fs::canonicalize(&path).unwrap_or_else(|_| path.clone());
+                if job.ancestors.contains(&canonical) {
+                    continue;
+                }
+                ancestors = Some(canonical);
+            }
+
+            if !self.is_walked(&path, depth, is_dir, rules.as_mut(), crossings) {
+                continue;
+            }
+            if self.is_listed(&path, depth, &metadata) {
+                walk_list.push(path.clone());
+            }
+
+            if is_dir && self.is_walked_into(depth) {
+                let mut child_rules = rules.clone();
+                if let Some(child_rules) = child_rules.as_mut() {
+                    child_rules.enter(&self.absolute.join(self.relative(&path)));
+                }
+
+                let mut child_ancestors = (*job.ancestors).clone();
+                child_ancestors.extend(ancestors);
+                children.push(Job { dir: path, depth, rules: child_rules, ancestors: Arc::new(child_ancestors) });
+            }
+        }
+
+        children
+    }
+}
+
+
+
+struct Job {
+    dir: PathBuf,
+    depth: usize,
+    rules: Option<IgnoreRules>,
+    //canonical paths of the walked directories above, only kept when following links
+    ancestors: Arc<Vec<PathBuf>>,
+}
+
+
+
+//pending directories and the number of them being read, the walk is over when both run out
+struct JobQueue {
+    state: Mutex<(Vec<Job>, usize)>,
+    changed: Condvar,
+}
+
+
+impl JobQueue {
+    fn new(job: Job) -> JobQueue {
+        JobQueue { state: Mutex::new((vec![job], 0)), changed: Condvar::new() }
+    }
+
+
+    fn pop(&self) -> Option<Job> {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if let Some(job) = state.0.pop() {
+                state.1 += 1;
+                return Some(job);
+            }
+            if state.1 == 0 {
+                return None;
+            }
+            state = self.changed.wait(state).unwrap();
+        }
+    }
+
+
+    fn finish(&self, children: Vec<Job>) {
+        let mut state = self.state.lock().unwrap();
+        state.0.extend(children);
+        state.1 -= 1;
+        self.changed.notify_all();
+    }
+}
+
+
+
+//filesystem a path lives on
+#[cfg(unix)]
+fn device_id(metadata: &Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    Some(metadata.dev())
+}
+
+
+#[cfg(not(unix))]
+fn device_id(_metadata: &Metadata) -> Option<u64> {
+    None
+}
+
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+
+    #[test]
+    fn parallel_walk_matches_sequential() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let root = tempdir.path();
+
+        for dir in &["a/b/c", "a/.cache", "b", "c d/e", "node_modules/x"] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        for file in &["a/1.jpg", "a/b/2.jpg", "a/b/c/3.txt", "a/.cache/4.jpg", "b/5.jpg", "c d/e/6.jpg", "node_modules/x/7.jpg", ".hidden.jpg"] {
+            fs::write(root.join(file), "").unwrap();
+        }
+
+        let filter = PathFilter::new(&[], &["node_modules/**"], None).unwrap();
+        let predicates = Predicates::default();
+        let roots = [WalkRoot { path: root.to_string_lossy().to_string(), min_depth: 1, max_depth: Some(3) }];
+
+        let walk_with = |threads| {
+            let options = WalkOptions { hidden: false, follow: false, one_file_system: false, filter: &filter, predicates: &predicates, ignore: false, threads };
+            walk(&roots, &options, &Printer::silent())
+        };
+
+        let sequential = walk_with(1);
+        assert_eq!(sequential.len(), 10);
+        assert!(!sequential.contains(&root.join("a/b/c/3.txt")));
+        assert!(!sequential.contains(&root.join("a/.cache")));
+
+        let mut sorted = sequential.clone();
+        sorted.sort();
+        assert_eq!(sequential, sorted);
+
+        for threads in 2..6 {
+            assert_eq!(walk_with(threads), sequential);
+        }
+    }
+
+
+
+    #[cfg(unix)]
+    #[test]
+    fn followed_links_are_listed_once() {
+        use crate::fileutils::create_symlink;
+
+        let tempdir = tempfile::tempdir().unwrap();
+        let root = tempdir.path();
+        fs::create_dir(root.join("real")).unwrap();
+        fs::write(root.join("real/a.txt"), "").unwrap();
+        create_symlink(Path::new("real"), &root.join("linked")).unwrap();
+        create_symlink(Path::new("real/a.txt"), &root.join("a_link.txt")).unwrap();
+
+        let filter = PathFilter::new(&[], &[], None).unwrap();
+        let predicates = Predicates::default();
+        let roots = [WalkRoot { path: root.to_string_lossy().to_string(), min_depth: 1, max_depth: None }];
+
+        for threads in 1..3 {
+            let options = WalkOptions { hidden: false, follow: true, one_file_system: false, filter: &filter, predicates: &predicates, ignore: false, threads };
+            let mut walk_list = walk(&roots, &options, &Printer::silent());
+            walk_list.sort();
+
+            let expected: PathList = ["a_link.txt", "linked", "real", "real/a.txt"].iter().map(|p| root.join(p)).collect();
+            assert_eq!(walk_list, expected);
+        }
+    }
+}